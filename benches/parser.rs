@@ -0,0 +1,108 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use regex::Regex;
+use std::hint::black_box;
+use syslog_sniffer::parse_syslog_packet;
+
+const RFC3164: &[&str] = &[
+    "<13>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick on /dev/pts/8",
+    "<38>Dec  3 08:00:01 web01 sshd[4242]: Accepted publickey for deploy from 10.0.0.7 port 52814 ssh2",
+    "<86>Jan 20 12:30:45 db-primary CRON[1234]: pam_unix(cron:session): session opened for user root",
+    "<4>Feb  1 00:00:00 fw01 kernel: [UFW BLOCK] IN=eth0 OUT= SRC=203.0.113.9 DST=10.0.0.1 PROTO=TCP DPT=22",
+];
+
+const RFC5424: &[&str] = &[
+    "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"] An application event log entry",
+    "<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - 'su root' failed for lonvick on /dev/pts/8",
+    "<14>1 2025-12-13T08:00:00.000Z app01.prod.example.com java 8812 - - {\"level\":\"info\",\"msg\":\"request served\",\"ms\":12}",
+];
+
+/// Messages without a recognisable header, including a long run of garbage
+fn unparsable() -> Vec<String> {
+    vec![
+        "<13>Hello world".to_string(),
+        "<999>1 2003-10-11T22:14:15.003Z host app - - - bad pri".to_string(),
+        "plain text from a misconfigured appliance".to_string(),
+        format!("<13>1 {}", "x ".repeat(2048)),
+    ]
+}
+
+/// The regex header matching `parse_syslog_packet` used before the hand-written scanner,
+/// including the copies it made of the message and hostname.
+struct RegexParser {
+    rfc5424: Regex,
+    rfc3164: Regex,
+}
+
+impl RegexParser {
+    fn new() -> Self {
+        Self {
+            rfc5424: Regex::new(r"^<(\d{1,3})>(\d)\s+(\S+)\s+(\S+)\s+").unwrap(),
+            rfc3164: Regex::new(
+                r"^<(\d{1,3})>([A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2})\s+(\S+)\s+",
+            )
+            .unwrap(),
+        }
+    }
+
+    fn parse(&self, packet: &[u8]) -> Option<(String, Option<String>)> {
+        let s = std::str::from_utf8(packet).ok()?;
+        let hostname = if let Some(caps) = self.rfc5424.captures(s) {
+            caps.get(4).map(|m| m.as_str().to_string())
+        } else {
+            self.rfc3164
+                .captures(s)
+                .and_then(|caps| caps.get(3))
+                .map(|m| m.as_str().to_string())
+        };
+        Some((s.to_string(), hostname))
+    }
+}
+
+fn bench_parsers(c: &mut Criterion) {
+    let unparsable = unparsable();
+    let mixed: Vec<&str> = RFC3164
+        .iter()
+        .chain(RFC5424)
+        .copied()
+        .chain(unparsable.iter().map(String::as_str))
+        .collect();
+    let mixes: [(&str, Vec<&str>); 4] = [
+        ("rfc3164", RFC3164.to_vec()),
+        ("rfc5424", RFC5424.to_vec()),
+        (
+            "unparsable",
+            unparsable.iter().map(String::as_str).collect(),
+        ),
+        ("mixed", mixed),
+    ];
+
+    let regex_parser = RegexParser::new();
+    let mut group = c.benchmark_group("parse");
+    for (name, messages) in &mixes {
+        let bytes: usize = messages.iter().map(|m| m.len()).sum();
+        group.throughput(Throughput::Bytes(bytes as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("scanner", name),
+            messages,
+            |b, messages| {
+                b.iter(|| {
+                    for message in messages {
+                        black_box(parse_syslog_packet(black_box(message.as_bytes())));
+                    }
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("regex", name), messages, |b, messages| {
+            b.iter(|| {
+                for message in messages {
+                    black_box(regex_parser.parse(black_box(message.as_bytes())));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parsers);
+criterion_main!(benches);
@@ -1,8 +1,131 @@
 use crate::config::Config;
+use crate::decode::decode_udp;
+use crate::framing::{split_messages, SplitMode};
+use crate::gelf::{self, GelfDecoder};
+use crate::grouping::Origin;
+use crate::hostname::HostNormalizer;
+use crate::reassembly::{Reassembler, DEFAULT_CONTINUATIONS};
+use crate::rules::RuleSet;
+use crate::sampling::SampleConfig;
 use crate::stats::StatsTracker;
-use crate::{parse_syslog_packet, PacketSource};
+use crate::{parse_syslog_packet_with, PacketSource, ParseOptions, SyslogPacket};
 use log::debug;
-use std::time::{Duration, Instant};
+use regex::Regex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Turns captured UDP payloads into parsed packets.
+struct PayloadParser {
+    split: SplitMode,
+    options: ParseOptions,
+    gelf_decoder: GelfDecoder,
+}
+
+impl PayloadParser {
+    fn new(config: &Config) -> Self {
+        Self {
+            split: config.split,
+            options: ParseOptions {
+                strict: config.strict,
+                unescape: config.unescape,
+            },
+            gelf_decoder: GelfDecoder::new(),
+        }
+    }
+
+    /// Parses a payload as GELF or syslog, calling `on_packet` for every message in it.
+    fn parse(&mut self, data: &[u8], mut on_packet: impl FnMut(SyslogPacket<'_>)) {
+        if gelf::is_gelf(data) {
+            let packet = self.gelf_decoder.decode(data, Instant::now());
+            // The checks are heuristics, anything that isn't a chunk and fails to decode
+            // may still be syslog, e.g. bare JSON or a message starting with "x "
+            if packet.is_some() || gelf::is_chunk(data) {
+                packet.into_iter().for_each(on_packet);
+                return;
+            }
+        }
+
+        for message in split_messages(data, self.split) {
+            // Heuristic: Syslog messages typically start with '<' (PRI).
+            // We try to find the start of the syslog message.
+            let message = match message.iter().position(|&b| b == b'<') {
+                Some(start_index) => &message[start_index..],
+                None => message,
+            };
+            parse_syslog_packet_with(message, &self.options)
+                .into_iter()
+                .for_each(&mut on_packet);
+        }
+    }
+}
+
+/// Normalises hostnames, applies extraction rules and counts parsed packets.
+struct Recorder {
+    stats: StatsTracker,
+    normalizer: HostNormalizer,
+    rules: RuleSet,
+}
+
+impl Recorder {
+    fn new(config: &Config) -> Self {
+        Self {
+            stats: StatsTracker::with_json_fields(config.json_fields.clone())
+                .with_hosts_file(config.hosts_file.clone())
+                .with_templates(config.templates)
+                .with_max_hosts(config.max_hosts)
+                .with_samples(SampleConfig {
+                    count: config.samples,
+                    by_severity: config.samples_by_severity,
+                    max_length: config.max_sample_length,
+                })
+                .with_groups(config.group_by.clone(), config.group_output),
+            normalizer: HostNormalizer::new(config.host_steps.clone(), config.hosts_file.clone()),
+            rules: config.rules.clone().unwrap_or_default(),
+        }
+    }
+
+    fn record(&mut self, mut syslog: SyslogPacket<'_>, origin: Origin) {
+        let hostname = self
+            .normalizer
+            .normalize(syslog.hostname.as_deref(), origin.source_ip);
+        let hostname = hostname.as_deref().unwrap_or("Unknown");
+        let extraction = self.rules.extract(hostname, &syslog);
+        syslog.extracted = extraction.fields;
+        self.stats.add_packet(hostname, &syslog);
+        self.stats.add_to_groups(hostname, &syslog, &origin);
+        if let Some(time) = origin.time {
+            self.stats.add_capture_time(hostname, time);
+        }
+        if !self.rules.is_empty() {
+            self.stats.add_rule_matches(hostname, &extraction.rules);
+        }
+        if let Some(source_ip) = origin.source_ip {
+            self.stats
+                .add_source(source_ip, syslog.hostname.as_deref(), hostname);
+        }
+        debug!("Captured from {}: {}", hostname, syslog.message);
+    }
+}
+
+/// The reassembly stage, if enabled, with the default continuation patterns unless
+/// others are configured.
+fn reassembler(config: &Config) -> Option<Reassembler<Origin>> {
+    if !config.reassemble {
+        return None;
+    }
+    let continuations = if config.continuations.is_empty() {
+        DEFAULT_CONTINUATIONS
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect()
+    } else {
+        config.continuations.clone()
+    };
+    Some(Reassembler::new(
+        continuations,
+        Duration::from_millis(config.reassembly_timeout),
+        config.reassembly_max_size,
+    ))
+}
 
 pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
     debug!("Port to sniff: {:?}", config.port);
@@ -15,7 +138,9 @@ pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
     let start_time = Instant::now();
     let duration = Duration::from_secs(config.interval);
 
-    let mut stats = StatsTracker::new();
+    let mut recorder = Recorder::new(&config);
+    let mut parser = PayloadParser::new(&config);
+    let mut reassembler = reassembler(&config);
     let mut last_report_time = Instant::now();
 
     loop {
@@ -23,11 +148,17 @@ pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
             break;
         }
 
+        if let Some(reassembler) = reassembler.as_mut() {
+            reassembler.flush_expired(Instant::now(), |packet, origin| {
+                recorder.record(packet, origin)
+            });
+        }
+
         if config.periodic && last_report_time.elapsed().as_secs() >= config.frequency {
-            if !stats.is_empty() {
-                let summary = stats.get_summary(config.frequency);
+            if !recorder.stats.is_empty() {
+                let summary = recorder.stats.get_summary(config.frequency);
                 println!("{}", serde_json::to_string_pretty(&summary).unwrap());
-                stats.clear();
+                recorder.stats.clear();
             }
             last_report_time = Instant::now();
         }
@@ -36,27 +167,23 @@ pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
             Ok(Some(packet)) => {
                 debug!("Received packet: len={}", packet.data.len());
 
-                // Heuristic: Syslog messages typically start with '<' (PRI).
-                // We try to find the start of the syslog message.
-                let syslog_msg =
-                    if let Some(start_index) = packet.data.iter().position(|&b| b == b'<') {
-                        parse_syslog_packet(&packet.data[start_index..])
-                    } else {
-                        parse_syslog_packet(&packet.data)
-                    };
-
-                if let Some(syslog) = syslog_msg {
-                    let hostname = syslog
-                        .hostname
-                        .clone()
-                        .unwrap_or_else(|| "Unknown".to_string());
-                    stats.add_entry(hostname.clone(), syslog.message.clone());
-                    debug!(
-                        "Captured from {}: {}",
-                        syslog.hostname.as_deref().unwrap_or("Unknown"),
-                        syslog.message
-                    );
-                }
+                // Frames we can't decode (e.g. non-Ethernet datalinks) are scanned as a whole
+                let datagram = decode_udp(&packet.data);
+                let payload = datagram.as_ref().map_or(&packet.data[..], |d| d.payload);
+                let origin = Origin {
+                    source_ip: datagram.as_ref().map(|d| d.src_ip),
+                    collector: datagram.as_ref().map(|d| d.dst_ip),
+                    vlan: datagram.as_ref().and_then(|d| d.vlan),
+                    time: Some(packet.timestamp.unwrap_or_else(SystemTime::now)),
+                };
+                parser.parse(payload, |syslog| match reassembler.as_mut() {
+                    Some(reassembler) => {
+                        reassembler.push(syslog, origin, Instant::now(), |packet, origin| {
+                            recorder.record(packet, origin)
+                        })
+                    }
+                    None => recorder.record(syslog, origin),
+                });
             }
             Ok(None) => {
                 // Timeout
@@ -69,13 +196,16 @@ pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
         }
     }
 
-    if !config.periodic || !stats.is_empty() {
+    if let Some(reassembler) = reassembler.as_mut() {
+        reassembler.flush(|packet, origin| recorder.record(packet, origin));
+    }
+    if !config.periodic || !recorder.stats.is_empty() {
         let interval = if config.periodic {
             last_report_time.elapsed().as_secs()
         } else {
             config.interval
         };
-        let summary = stats.get_summary(interval);
+        let summary = recorder.stats.get_summary(interval);
         println!("{}", serde_json::to_string_pretty(&summary).unwrap());
     }
 }
@@ -83,7 +213,10 @@ pub fn run_sniffer<S: PacketSource>(config: Config, mut source: S) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::grouping::GroupKey;
+    use crate::hostname::HostStep;
     use crate::PacketData;
+    use clap::Parser;
     use std::collections::VecDeque;
 
     struct MockPacketSource {
@@ -108,6 +241,10 @@ mod tests {
         }
     }
 
+    fn base_config() -> Config {
+        Config::parse_from(["syslog_sniffer", "--interface", "lo"])
+    }
+
     fn init_logger() {
         let _ = env_logger::builder().is_test(true).try_init();
     }
@@ -122,13 +259,17 @@ mod tests {
             debug: false,
             periodic: false,
             frequency: 0,
+            ..base_config()
         };
 
         let packet_data = "<13>Oct 11 22:14:15 mymachine su: su root"
             .as_bytes()
             .to_vec();
         let packets = vec![
-            Ok(Some(PacketData { data: packet_data })),
+            Ok(Some(PacketData {
+                data: packet_data,
+                timestamp: None,
+            })),
             Ok(None), // Simulate timeout
         ];
 
@@ -146,6 +287,7 @@ mod tests {
             debug: true,
             periodic: true,
             frequency: 0, // Report every iteration (effectively)
+            ..base_config()
         };
 
         let packet_data = "<13>Oct 11 22:14:15 mymachine su: su root"
@@ -155,8 +297,12 @@ mod tests {
         let packets = vec![
             Ok(Some(PacketData {
                 data: packet_data.clone(),
+                timestamp: None,
+            })),
+            Ok(Some(PacketData {
+                data: packet_data,
+                timestamp: None,
             })),
-            Ok(Some(PacketData { data: packet_data })),
             Ok(None),
         ];
 
@@ -173,13 +319,18 @@ mod tests {
             debug: false,
             periodic: false,
             frequency: 5,
+            ..base_config()
         };
 
         let packets = vec![
             Ok(Some(PacketData {
                 data: vec![0, 1, 2, 3],
+                timestamp: None,
             })), // Invalid syslog
-            Ok(Some(PacketData { data: vec![] })), // Empty
+            Ok(Some(PacketData {
+                data: vec![],
+                timestamp: None,
+            })), // Empty
             Ok(None),
         ];
 
@@ -196,6 +347,7 @@ mod tests {
             debug: false,
             periodic: false,
             frequency: 5,
+            ..base_config()
         };
 
         let packets = vec![Err("Simulated error".to_string()), Ok(None)];
@@ -212,10 +364,299 @@ mod tests {
             debug: true,
             periodic: false,
             frequency: 5,
+            ..base_config()
         };
 
         let packet_data = "Simple message without hostname".as_bytes().to_vec();
-        let packets = vec![Ok(Some(PacketData { data: packet_data })), Ok(None)];
+        let packets = vec![
+            Ok(Some(PacketData {
+                data: packet_data,
+                timestamp: None,
+            })),
+            Ok(None),
+        ];
+
+        let source = MockPacketSource::new(packets);
+        run_sniffer(config, source);
+    }
+
+    fn parse_all(parser: &mut PayloadParser, data: &[u8]) -> Vec<crate::OwnedSyslogPacket> {
+        let mut packets = Vec::new();
+        parser.parse(data, |packet| packets.push(packet.into_owned()));
+        packets
+    }
+
+    #[test]
+    fn test_parse_payload() {
+        let mut parser = PayloadParser::new(&base_config());
+
+        let gelf = br#"{"version":"1.1","host":"app01","short_message":"hi"}"#;
+        let packets = parse_all(&mut parser, gelf);
+        assert_eq!(packets[0].format, crate::MessageFormat::Gelf);
+        assert_eq!(packets[0].hostname.as_deref(), Some("app01"));
+
+        let packets = parse_all(&mut parser, br#"{"user":"alice"}"#);
+        assert_eq!(packets[0].format, crate::MessageFormat::Unknown);
+        assert!(packets[0].structured.is_some());
+
+        // Looks like a zlib header but isn't GELF
+        assert!(gelf::is_gelf(b"x marks the spot"));
+        let packets = parse_all(&mut parser, b"x marks the spot");
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].msg, "x marks the spot");
+
+        // A partial chunk yields nothing and isn't mistaken for syslog
+        parser.split = SplitMode::Lf;
+        let chunk = [0x1e, 0x0f, 1, 1, 1, 1, 1, 1, 1, 1, 0, 2, b'<', b'1', b'>'];
+        assert!(parse_all(&mut parser, &chunk).is_empty());
+
+        let frame = crate::decode::tests::udp_frame([10, 0, 0, 1], [10, 0, 0, 2], None, gelf);
+        let payload = decode_udp(&frame).unwrap().payload;
+        assert_eq!(parse_all(&mut parser, payload).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_payload_split() {
+        let mut parser = PayloadParser::new(&base_config());
+        let batch = b"<13>Oct 11 22:14:15 host1 su: a\n<13>Oct 11 22:14:16 host2 su: b\n";
+
+        let packets = parse_all(&mut parser, batch);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].hostname.as_deref(), Some("host1"));
+        assert_eq!(packets[1].hostname.as_deref(), Some("host2"));
+
+        parser.split = SplitMode::None;
+        assert_eq!(parse_all(&mut parser, batch).len(), 1);
+
+        parser.split = SplitMode::OctetCounted;
+        let framed = b"34 <13>Oct 11 22:14:15 host1 su: a34 <13>Oct 11 22:14:16 host2 su: b";
+        let packets = parse_all(&mut parser, framed);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].message, "<13>Oct 11 22:14:16 host2 su: b");
+    }
+
+    #[test]
+    fn test_parse_payload_strict() {
+        let config = Config {
+            strict: true,
+            ..base_config()
+        };
+        let mut parser = PayloadParser::new(&config);
+        let packets = parse_all(&mut parser, b"<13>Oct 01 22:14:15 host1 su: a");
+        assert_eq!(
+            packets[0].violations,
+            vec![crate::compliance::Violation::BadTimestamp]
+        );
+    }
+
+    #[test]
+    fn test_parse_payload_unescape() {
+        let payload = b"<11>Oct 11 22:14:15 app01 java: Error#012#011at Main.run";
+        let packets = parse_all(&mut PayloadParser::new(&base_config()), payload);
+        assert_eq!(packets[0].msg, "Error#012#011at Main.run");
+
+        let config = Config {
+            unescape: true,
+            ..base_config()
+        };
+        let packets = parse_all(&mut PayloadParser::new(&config), payload);
+        assert_eq!(packets[0].msg, "Error\n\tat Main.run");
+        assert_eq!(
+            packets[0].message,
+            "<11>Oct 11 22:14:15 app01 java: Error\n\tat Main.run"
+        );
+        assert_eq!(packets[0].escapes, vec![crate::escapes::Escape::Octal]);
+        // Sizes are counted as received
+        assert_eq!(packets[0].size, payload.len());
+        let mut stats = StatsTracker::new();
+        stats.add_packet("app01", &packets[0]);
+        assert_eq!(
+            stats.get_summary(10).hosts["app01"].bytes,
+            payload.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_run_sniffer_gelf() {
+        let config = Config {
+            interval: 1,
+            ..base_config()
+        };
+
+        let gelf = br#"{"version":"1.1","host":"app01","short_message":"hi","level":3}"#;
+        let frame = crate::decode::tests::udp_frame([10, 0, 0, 1], [10, 0, 0, 2], None, gelf);
+        let packets = vec![
+            Ok(Some(PacketData {
+                data: frame,
+                timestamp: None,
+            })),
+            Ok(None),
+        ];
+
+        let source = MockPacketSource::new(packets);
+        run_sniffer(config, source);
+    }
+
+    #[test]
+    fn test_run_sniffer_json_fields() {
+        let config = Config {
+            interval: 1,
+            json_fields: vec!["/user".to_string()],
+            ..base_config()
+        };
+
+        let packet_data = r#"<13>Oct 11 22:14:15 mymachine app: @cee: {"user":"alice"}"#
+            .as_bytes()
+            .to_vec();
+        let packets = vec![
+            Ok(Some(PacketData {
+                data: packet_data,
+                timestamp: None,
+            })),
+            Ok(None),
+        ];
+
+        let source = MockPacketSource::new(packets);
+        run_sniffer(config, source);
+    }
+
+    #[test]
+    fn test_run_sniffer_host_normalization() {
+        let config = Config {
+            interval: 1,
+            host_steps: vec![HostStep::Lowercase, HostStep::Nilvalue, HostStep::SourceIp],
+            ..base_config()
+        };
+
+        let nil_host = b"<165>1 2003-10-11T22:14:15.003Z - su - - - no hostname";
+        let packets = [&b"<13>Oct 11 22:14:15 WEB01 su: su root"[..], nil_host]
+            .into_iter()
+            .map(|payload| {
+                let frame =
+                    crate::decode::tests::udp_frame([10, 0, 0, 1], [10, 0, 0, 2], None, payload);
+                Ok(Some(PacketData {
+                    data: frame,
+                    timestamp: None,
+                }))
+            })
+            .chain([Ok(None)])
+            .collect();
+
+        let source = MockPacketSource::new(packets);
+        run_sniffer(config, source);
+    }
+
+    #[test]
+    fn test_run_sniffer_rules() {
+        let rules =
+            r#"[{"name": "status", "pattern": "status=(?P<status>\\d+)", "apps": ["nginx"]}]"#;
+        let config = Config {
+            interval: 1,
+            rules: Some(crate::rules::RuleSet::parse(rules).unwrap()),
+            ..base_config()
+        };
+
+        let packet_data = b"<13>Oct 11 22:14:15 web01 nginx: status=500".to_vec();
+        let packets = vec![
+            Ok(Some(PacketData {
+                data: packet_data,
+                timestamp: None,
+            })),
+            Ok(None),
+        ];
+
+        let source = MockPacketSource::new(packets);
+        run_sniffer(config, source);
+    }
+
+    #[test]
+    fn test_reassembly_counts_events() {
+        assert!(reassembler(&base_config()).is_none());
+
+        let config = Config {
+            reassemble: true,
+            ..base_config()
+        };
+        let mut recorder = Recorder::new(&config);
+        let mut reassembler = reassembler(&config).unwrap();
+        for line in [
+            "java.lang.Error: boom",
+            "\tat Main.run(Main.java:5)",
+            "\tat Main.main(Main.java:1)",
+        ] {
+            let message = format!("<11>Oct 11 22:14:15 app01 java[42]: {line}");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            reassembler.push(
+                packet,
+                Origin::default(),
+                Instant::now(),
+                |packet, origin| recorder.record(packet, origin),
+            );
+        }
+        reassembler.flush(|packet, origin| recorder.record(packet, origin));
+
+        let summary = recorder.stats.get_summary(1);
+        assert_eq!(summary.hosts["app01"].count, 1);
+        assert_eq!(
+            summary.hosts["app01"].sample,
+            "<11>Oct 11 22:14:15 app01 java[42]: java.lang.Error: boom\n\tat Main.run(Main.java:5)\n\tat Main.main(Main.java:1)"
+        );
+    }
+
+    #[test]
+    fn test_record_groups() {
+        let config = Config {
+            group_by: vec![GroupKey::Collector, GroupKey::Vlan, GroupKey::Host],
+            ..base_config()
+        };
+        let mut recorder = Recorder::new(&config);
+        let origin = Origin {
+            source_ip: Some("10.0.0.5".parse().unwrap()),
+            collector: Some("10.0.0.1".parse().unwrap()),
+            vlan: Some(7),
+            time: None,
+        };
+        for message in [
+            "<13>Oct 11 22:14:15 web01 a: x",
+            "<13>Oct 11 22:14:15 web02 a: x",
+        ] {
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            recorder.record(packet, origin);
+        }
+        recorder.record(
+            crate::parse_syslog_packet(b"<13>Oct 11 22:14:15 web01 a: y").unwrap(),
+            Origin::default(),
+        );
+
+        assert_eq!(
+            recorder.stats.get_summary(1).groups,
+            Some(serde_json::json!({
+                "10.0.0.1": {"7": {"web01": 1, "web02": 1}},
+                "-": {"-": {"web01": 1}}
+            }))
+        );
+    }
+
+    #[test]
+    fn test_run_sniffer_reassembly() {
+        let config = Config {
+            interval: 1,
+            reassemble: true,
+            ..base_config()
+        };
+        let packets = [
+            &b"<11>Oct 11 22:14:15 app01 java: java.lang.Error: boom"[..],
+            b"<11>Oct 11 22:14:15 app01 java: \tat Main.run(Main.java:5)",
+        ]
+        .into_iter()
+        .map(|payload| {
+            Ok(Some(PacketData {
+                data: payload.to_vec(),
+                timestamp: None,
+            }))
+        })
+        .chain([Ok(None)])
+        .collect();
 
         let source = MockPacketSource::new(packets);
         run_sniffer(config, source);
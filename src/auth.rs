@@ -0,0 +1,318 @@
+use crate::events::key_values;
+use crate::stats::count_capped;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// User names counted per host or source, further names are counted as `(other)`
+const MAX_USERS: usize = 100;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthOutcome {
+    Success,
+    Failure,
+    /// A login attempt for an account that doesn't exist
+    InvalidUser,
+}
+
+/// A login, sudo invocation or PAM failure.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AuthEvent {
+    /// `sshd`, `sudo`, or the service a PAM module reported for
+    pub service: String,
+    /// e.g. `password` or `publickey`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_ip: Option<String>,
+    pub outcome: AuthOutcome,
+    /// The user sudo runs the command as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl AuthEvent {
+    fn new(service: &str, outcome: AuthOutcome) -> Self {
+        Self {
+            service: service.to_string(),
+            method: None,
+            user: None,
+            source_ip: None,
+            outcome,
+            target_user: None,
+            command: None,
+        }
+    }
+}
+
+/// Recognises sshd, sudo and PAM authentication messages.
+pub fn parse_auth(msg: &str) -> Option<AuthEvent> {
+    parse_sshd(msg)
+        .or_else(|| parse_sudo(msg))
+        .or_else(|| parse_pam(msg))
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// `Accepted publickey for alice from 10.0.0.1 port 5000 ssh2`,
+/// `Failed password for invalid user bob from 10.0.0.1 port 5000 ssh2` and
+/// `Invalid user bob from 10.0.0.1 port 5000`
+fn parse_sshd(msg: &str) -> Option<AuthEvent> {
+    let (mut event, rest) = if let Some(rest) = msg.strip_prefix("Invalid user ") {
+        (AuthEvent::new("sshd", AuthOutcome::InvalidUser), rest)
+    } else {
+        let (outcome, rest) = match msg.strip_prefix("Accepted ") {
+            Some(rest) => (AuthOutcome::Success, rest),
+            None => (AuthOutcome::Failure, msg.strip_prefix("Failed ")?),
+        };
+        let (method, rest) = rest.split_once(" for ")?;
+        if method.contains(' ') {
+            return None;
+        }
+        let mut event = AuthEvent::new("sshd", outcome);
+        event.method = non_empty(method);
+        match rest.strip_prefix("invalid user ") {
+            Some(rest) => {
+                event.outcome = AuthOutcome::InvalidUser;
+                (event, rest)
+            }
+            None => (event, rest),
+        }
+    };
+
+    // User names may be empty or contain spaces, the address follows the last ` from `
+    let (user, rest) = rest.rsplit_once(" from ")?;
+    event.user = non_empty(user);
+    event.source_ip = non_empty(rest.split(' ').next().unwrap_or_default());
+    Some(event)
+}
+
+/// `alice : TTY=pts/0 ; PWD=/home/alice ; USER=root ; COMMAND=/bin/ls`, where a
+/// failure puts its reason first, e.g. `3 incorrect password attempts ; TTY=...`
+fn parse_sudo(msg: &str) -> Option<AuthEvent> {
+    let (user, rest) = msg.trim_start().split_once(" : ")?;
+    let (details, command) = rest.split_once("COMMAND=")?;
+    if user.contains(' ') || !details.contains("USER=") {
+        return None;
+    }
+
+    let mut event = AuthEvent::new("sudo", AuthOutcome::Success);
+    event.user = non_empty(user);
+    event.command = non_empty(command.trim());
+    for (index, part) in details.split(" ; ").map(str::trim).enumerate() {
+        if let Some(target) = part.strip_prefix("USER=") {
+            event.target_user = non_empty(target);
+        } else if index == 0 && !part.contains('=') {
+            event.outcome = AuthOutcome::Failure;
+        }
+    }
+    Some(event)
+}
+
+/// `pam_unix(sudo:auth): authentication failure; logname= uid=0 rhost=10.0.0.1 user=root`.
+/// sshd's own `Failed password` line follows its PAM failures, so those are skipped
+/// to count each attempt once.
+fn parse_pam(msg: &str) -> Option<AuthEvent> {
+    let start = msg.find("pam_")?;
+    let (module, details) = msg[start..].split_once("): authentication failure;")?;
+    let (_, service) = module.split_once('(')?;
+    let service = service.split(':').next().unwrap_or_default();
+    if service == "sshd" {
+        return None;
+    }
+
+    let mut event = AuthEvent::new(service, AuthOutcome::Failure);
+    for (key, value) in key_values(details) {
+        match key {
+            "user" => event.user = non_empty(value),
+            "rhost" => event.source_ip = non_empty(value),
+            _ => {}
+        }
+    }
+    Some(event)
+}
+
+/// Authentication outcomes for a host or a source address.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct AuthStats {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub success: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub failure: u64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub invalid_user: u64,
+    /// Events per user name, names beyond the first 100 counted as `(other)`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub users: HashMap<String, u64>,
+    /// Events per host, only tracked for source addresses
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub hosts: HashMap<String, u64>,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+impl AuthStats {
+    pub fn is_empty(&self) -> bool {
+        self.success == 0 && self.failure == 0 && self.invalid_user == 0
+    }
+
+    /// Failed attempts, whether or not the user exists
+    pub fn failed(&self) -> u64 {
+        self.failure + self.invalid_user
+    }
+
+    pub fn record(&mut self, event: &AuthEvent) {
+        match event.outcome {
+            AuthOutcome::Success => self.success += 1,
+            AuthOutcome::Failure => self.failure += 1,
+            AuthOutcome::InvalidUser => self.invalid_user += 1,
+        }
+        if let Some(user) = &event.user {
+            count_capped(&mut self.users, user, MAX_USERS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sshd() {
+        for (msg, method, user, ip, outcome) in [
+            (
+                "Accepted publickey for alice from 10.0.0.1 port 5000 ssh2: ED25519 SHA256:abc",
+                Some("publickey"),
+                Some("alice"),
+                "10.0.0.1",
+                AuthOutcome::Success,
+            ),
+            (
+                "Failed password for root from 203.0.113.5 port 22 ssh2",
+                Some("password"),
+                Some("root"),
+                "203.0.113.5",
+                AuthOutcome::Failure,
+            ),
+            (
+                "Failed password for invalid user admin from 2001:db8::1 port 22 ssh2",
+                Some("password"),
+                Some("admin"),
+                "2001:db8::1",
+                AuthOutcome::InvalidUser,
+            ),
+            (
+                "Invalid user  from 203.0.113.5 port 4711",
+                None,
+                None,
+                "203.0.113.5",
+                AuthOutcome::InvalidUser,
+            ),
+        ] {
+            let event = parse_auth(msg).unwrap();
+            assert_eq!(event.service, "sshd", "{msg}");
+            assert_eq!(event.method.as_deref(), method, "{msg}");
+            assert_eq!(event.user.as_deref(), user, "{msg}");
+            assert_eq!(event.source_ip.as_deref(), Some(ip), "{msg}");
+            assert_eq!(event.outcome, outcome, "{msg}");
+        }
+
+        assert_eq!(parse_auth("Failed to start Apache for real"), None);
+        assert_eq!(parse_auth("Accepted connection for alice"), None);
+    }
+
+    #[test]
+    fn test_parse_sudo() {
+        let event = parse_auth(
+            "alice : TTY=pts/0 ; PWD=/home/alice ; USER=root ; COMMAND=/usr/bin/apt update",
+        )
+        .unwrap();
+        assert_eq!(event.service, "sudo");
+        assert_eq!(event.user.as_deref(), Some("alice"));
+        assert_eq!(event.target_user.as_deref(), Some("root"));
+        assert_eq!(event.command.as_deref(), Some("/usr/bin/apt update"));
+        assert_eq!(event.outcome, AuthOutcome::Success);
+        assert_eq!(event.source_ip, None);
+
+        let event = parse_auth(
+            "   bob : 3 incorrect password attempts ; TTY=pts/1 ; PWD=/ ; USER=root ; COMMAND=/bin/sh",
+        )
+        .unwrap();
+        assert_eq!(event.user.as_deref(), Some("bob"));
+        assert_eq!(event.outcome, AuthOutcome::Failure);
+
+        assert_eq!(parse_auth("note : COMMAND= is not a sudo line"), None);
+    }
+
+    #[test]
+    fn test_parse_pam() {
+        let event = parse_auth(
+            "pam_unix(login:auth): authentication failure; logname= uid=0 euid=0 tty=tty1 ruser= rhost=203.0.113.5  user=root",
+        )
+        .unwrap();
+        assert_eq!(event.service, "login");
+        assert_eq!(event.user.as_deref(), Some("root"));
+        assert_eq!(event.source_ip.as_deref(), Some("203.0.113.5"));
+        assert_eq!(event.outcome, AuthOutcome::Failure);
+
+        let event = parse_auth(
+            "pam_unix(sudo:auth): authentication failure; logname=alice rhost=  user=alice",
+        )
+        .unwrap();
+        assert_eq!(event.service, "sudo");
+        assert_eq!(event.source_ip, None);
+
+        assert_eq!(parse_auth("pam_unix(cron:session): session opened"), None);
+    }
+
+    #[test]
+    fn test_sshd_failure_counted_once() {
+        let mut stats = AuthStats::default();
+        for msg in [
+            "pam_unix(sshd:auth): authentication failure; logname= uid=0 euid=0 tty=ssh ruser= rhost=203.0.113.5  user=root",
+            "Failed password for root from 203.0.113.5 port 22 ssh2",
+        ] {
+            if let Some(event) = parse_auth(msg) {
+                stats.record(&event);
+            }
+        }
+        assert_eq!(stats.failure, 1);
+        assert_eq!(stats.users["root"], 1);
+    }
+
+    #[test]
+    fn test_auth_stats() {
+        let mut stats = AuthStats::default();
+        assert!(stats.is_empty());
+        for msg in [
+            "Failed password for root from 203.0.113.5 port 22 ssh2",
+            "Failed password for root from 203.0.113.5 port 22 ssh2",
+            "Invalid user admin from 203.0.113.5 port 22",
+            "Accepted password for root from 203.0.113.5 port 22 ssh2",
+        ] {
+            stats.record(&parse_auth(msg).unwrap());
+        }
+        assert_eq!(
+            (stats.success, stats.failure, stats.invalid_user),
+            (1, 2, 1)
+        );
+        assert_eq!(stats.failed(), 3);
+        assert_eq!(stats.users["root"], 3);
+
+        for i in 0..MAX_USERS + 5 {
+            let msg = format!("Invalid user u{i} from 203.0.113.5 port 22");
+            stats.record(&parse_auth(&msg).unwrap());
+        }
+        assert_eq!(stats.users.len(), MAX_USERS + 1);
+        assert_eq!(stats.users[crate::stats::OTHER_VALUES], 7);
+        assert_eq!(stats.invalid_user, MAX_USERS as u64 + 6);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{PacketData, PacketSource};
-use pcap::{Active, Capture, Device};
+use pcap::{Active, Capture, Device, PacketHeader};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct PcapCapture {
     capture: Capture<Active>,
@@ -37,6 +38,7 @@ impl PacketSource for PcapCapture {
         match self.capture.next_packet() {
             Ok(packet) => Ok(Some(PacketData {
                 data: packet.data.to_vec(),
+                timestamp: capture_time(packet.header),
             })),
             Err(pcap::Error::TimeoutExpired) => Ok(None),
             Err(e) => Err(format!("Error capturing packet: {:?}", e)),
@@ -48,6 +50,13 @@ impl PacketSource for PcapCapture {
     }
 }
 
+/// The capture timestamp of a packet header.
+fn capture_time(header: &PacketHeader) -> Option<SystemTime> {
+    let secs = u64::try_from(header.ts.tv_sec).ok()?;
+    let micros = u64::try_from(header.ts.tv_usec).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros))
+}
+
 pub fn setup_capture(interface: &str, port: usize) -> Result<PcapCapture, String> {
     PcapCapture::new(interface, port)
 }
@@ -0,0 +1,84 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 2^PRECISION registers of one byte each, for a standard error of 1.04/sqrt(1024),
+/// about 3%
+const PRECISION: u32 = 10;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// Estimates the number of distinct values added in fixed memory, after the
+/// HyperLogLog algorithm (Flajolet et al., 2007).
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: [u8; REGISTERS],
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: [0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn add(&mut self, value: impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        self.add_hash(hasher.finish());
+    }
+
+    /// Adds a value by its 64-bit hash.
+    pub fn add_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // Position of the first set bit after the index bits, the sentinel bit caps it
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        let register = &mut self.registers[index];
+        *register = (*register).max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&register| 2f64.powi(-i32::from(register)))
+            .sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|&&register| register == 0)
+            .count();
+        // Linear counting is more accurate while many registers are still empty
+        if estimate <= 2.5 * m && zeros > 0 {
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+        estimate.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let mut hll = HyperLogLog::default();
+        assert_eq!(hll.estimate(), 0);
+        for _ in 0..1000 {
+            hll.add("same message");
+        }
+        assert_eq!(hll.estimate(), 1);
+
+        for exact in [100, 10_000, 200_000] {
+            let mut hll = HyperLogLog::default();
+            for i in 0..exact {
+                hll.add(i);
+                hll.add(i);
+            }
+            let error = (hll.estimate() as f64 - exact as f64).abs() / exact as f64;
+            assert!(error < 0.1, "{exact}: {}", hll.estimate());
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use serde::Serialize;
+
+/// RFC 3164 caps the whole packet at 1024 bytes
+const RFC3164_MAX_LEN: usize = 1024;
+/// RFC 5424 header field limits
+pub(crate) const MAX_HOSTNAME_LEN: usize = 255;
+pub(crate) const MAX_APP_NAME_LEN: usize = 48;
+pub(crate) const MAX_PROCID_LEN: usize = 128;
+pub(crate) const MAX_MSGID_LEN: usize = 32;
+pub(crate) const MAX_SD_NAME_LEN: usize = 32;
+/// RFC 3164 month abbreviations, shared by the parser and serializer
+pub(crate) const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A specific way a message deviates from RFC 3164 or RFC 5424.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Violation {
+    /// No `<PRI>` at the start of the message, or it isn't terminated
+    MissingPri,
+    /// PRI above 191
+    PriOutOfRange,
+    /// PRI written with leading zeros, e.g. `<013>`
+    PriLeadingZero,
+    /// RFC 5424 timestamp directly after the PRI, without `1 `
+    MissingVersion,
+    /// A VERSION other than 1
+    UnsupportedVersion,
+    /// Timestamp that isn't RFC 3339 (RFC 5424) or `Mmm dd hh:mm:ss` (RFC 3164)
+    BadTimestamp,
+    /// Header ends before all fields are present
+    TruncatedHeader,
+    /// Hostname with characters outside letters, digits, `-`, `.` and `:`
+    InvalidHostnameChars,
+    /// Empty header field where `-` is required, or `-` run into the next field
+    NilvalueMisuse,
+    /// Non-printable or non-ASCII bytes in APP-NAME, PROCID or MSGID
+    NonPrintableHeader,
+    HostnameTooLong,
+    AppNameTooLong,
+    ProcIdTooLong,
+    MsgIdTooLong,
+    /// RFC 3164 message over 1024 bytes
+    MessageTooLong,
+    /// SD-ID or PARAM-NAME that is empty, too long or contains `=`, space, `]` or `"`
+    IllegalSdName,
+    /// `"`, `\` or `]` left unescaped in a PARAM-VALUE
+    UnescapedSdValue,
+    /// STRUCTURED-DATA that doesn't follow `[id name="value" ...]`
+    MalformedStructuredData,
+}
+
+/// Checks a message against RFC 5424, or RFC 3164 if it carries a BSD timestamp.
+pub fn check(message: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let Some(rest) = check_pri(message, &mut violations) else {
+        return violations;
+    };
+
+    if rest.starts_with(|c: char| c.is_ascii_uppercase()) {
+        if message.len() > RFC3164_MAX_LEN {
+            violations.push(Violation::MessageTooLong);
+        }
+        check_rfc3164(rest, &mut violations);
+    } else {
+        check_rfc5424(rest, &mut violations);
+    }
+    violations
+}
+
+/// Returns the input after the PRI, or `None` if there is no usable PRI.
+fn check_pri<'a>(message: &'a str, violations: &mut Vec<Violation>) -> Option<&'a str> {
+    let Some((digits, rest)) = message
+        .strip_prefix('<')
+        .and_then(|rest| rest.split_once('>'))
+        .filter(|(digits, _)| (1..=3).contains(&digits.len()))
+        .filter(|(digits, _)| digits.bytes().all(|b| b.is_ascii_digit()))
+    else {
+        violations.push(Violation::MissingPri);
+        return None;
+    };
+
+    if digits.len() > 1 && digits.starts_with('0') {
+        violations.push(Violation::PriLeadingZero);
+    }
+    if digits.parse::<u16>().is_ok_and(|pri| pri > 191) {
+        violations.push(Violation::PriOutOfRange);
+    }
+    Some(rest)
+}
+
+/// Splits off the next space separated header field.
+fn next_field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    if rest.is_empty() {
+        return None;
+    }
+    let (field, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+    *rest = tail;
+    Some(field)
+}
+
+fn check_rfc5424(mut rest: &str, violations: &mut Vec<Violation>) {
+    let Some(mut field) = next_field(&mut rest) else {
+        violations.push(Violation::TruncatedHeader);
+        return;
+    };
+
+    // A date where the version should be: the sender left out `1 `
+    if field.len() > 4 && field.as_bytes()[4] == b'-' {
+        violations.push(Violation::MissingVersion);
+    } else {
+        match field {
+            "" | "-" => violations.push(Violation::NilvalueMisuse),
+            "1" => {}
+            _ => violations.push(Violation::UnsupportedVersion),
+        }
+        let Some(timestamp) = next_field(&mut rest) else {
+            violations.push(Violation::TruncatedHeader);
+            return;
+        };
+        field = timestamp;
+    }
+
+    if field.is_empty() {
+        violations.push(Violation::NilvalueMisuse);
+    } else if field != "-" && !valid_rfc3339(field) {
+        violations.push(Violation::BadTimestamp);
+    }
+
+    let limits = [
+        (MAX_HOSTNAME_LEN, Violation::HostnameTooLong),
+        (MAX_APP_NAME_LEN, Violation::AppNameTooLong),
+        (MAX_PROCID_LEN, Violation::ProcIdTooLong),
+        (MAX_MSGID_LEN, Violation::MsgIdTooLong),
+    ];
+    for (index, (max_len, too_long)) in limits.into_iter().enumerate() {
+        let Some(field) = next_field(&mut rest) else {
+            violations.push(Violation::TruncatedHeader);
+            return;
+        };
+        if field.is_empty() {
+            violations.push(Violation::NilvalueMisuse);
+            continue;
+        }
+        if field.len() > max_len {
+            violations.push(too_long);
+        }
+        if index == 0 {
+            if field != "-" && !valid_hostname(field) {
+                violations.push(Violation::InvalidHostnameChars);
+            }
+        } else if !field.bytes().all(|b| b.is_ascii_graphic()) {
+            violations.push(Violation::NonPrintableHeader);
+        }
+    }
+
+    check_structured_data(rest, violations);
+}
+
+fn check_structured_data(sd: &str, violations: &mut Vec<Violation>) {
+    if let Some(after) = sd.strip_prefix('-') {
+        if !after.is_empty() && !after.starts_with(' ') {
+            violations.push(Violation::NilvalueMisuse);
+        }
+        return;
+    }
+    if sd.is_empty() {
+        violations.push(Violation::TruncatedHeader);
+        return;
+    }
+
+    let mut rest = sd;
+    while let Some(element) = rest.strip_prefix('[') {
+        match check_sd_element(element, violations) {
+            Some(tail) => rest = tail,
+            None => {
+                violations.push(Violation::MalformedStructuredData);
+                return;
+            }
+        }
+    }
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        violations.push(Violation::MalformedStructuredData);
+    }
+}
+
+/// Checks one SD-ELEMENT after its `[`, returning the input after the closing `]`.
+fn check_sd_element<'a>(element: &'a str, violations: &mut Vec<Violation>) -> Option<&'a str> {
+    let end = element.find([' ', ']'])?;
+    check_sd_name(&element[..end], violations);
+    let mut rest = &element[end..];
+
+    while let Some(param) = rest.strip_prefix(' ') {
+        let (name, value) = param.split_once('=')?;
+        check_sd_name(name, violations);
+        rest = check_param_value(value.strip_prefix('"')?, violations)?;
+    }
+    rest.strip_prefix(']')
+}
+
+fn check_sd_name(name: &str, violations: &mut Vec<Violation>) {
+    let legal = |b: u8| b.is_ascii_graphic() && !matches!(b, b'=' | b']' | b'"');
+    if name.is_empty() || name.len() > MAX_SD_NAME_LEN || !name.bytes().all(legal) {
+        violations.push(Violation::IllegalSdName);
+    }
+}
+
+/// Scans a PARAM-VALUE after its opening quote, returning the input after the closing quote.
+fn check_param_value<'a>(value: &'a str, violations: &mut Vec<Violation>) -> Option<&'a str> {
+    let bytes = value.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' if matches!(bytes.get(index + 1), Some(b'"' | b'\\' | b']')) => index += 2,
+            b'\\' => {
+                // A lone backslash must itself be escaped
+                violations.push(Violation::UnescapedSdValue);
+                index += 1;
+            }
+            b']' => {
+                violations.push(Violation::UnescapedSdValue);
+                index += 1;
+            }
+            b'"' => return Some(&value[index + 1..]),
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+fn check_rfc3164(rest: &str, violations: &mut Vec<Violation>) {
+    // "Mmm dd hh:mm:ss " is exactly 16 bytes, days below 10 are space padded
+    let Some(timestamp) = rest.get(..15) else {
+        violations.push(Violation::BadTimestamp);
+        return;
+    };
+    if !valid_bsd_timestamp(timestamp) || rest.as_bytes().get(15) != Some(&b' ') {
+        violations.push(Violation::BadTimestamp);
+        return;
+    }
+
+    let mut rest = &rest[16..];
+    match next_field(&mut rest) {
+        None => violations.push(Violation::TruncatedHeader),
+        Some("") => violations.push(Violation::NilvalueMisuse),
+        Some(hostname) => {
+            if !valid_hostname(hostname) {
+                violations.push(Violation::InvalidHostnameChars);
+            }
+            if hostname.len() > MAX_HOSTNAME_LEN {
+                violations.push(Violation::HostnameTooLong);
+            }
+        }
+    }
+}
+
+fn valid_hostname(hostname: &str) -> bool {
+    hostname
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b':'))
+}
+
+fn number(s: &str, range: std::ops::RangeInclusive<u32>) -> bool {
+    !s.is_empty()
+        && s.bytes().all(|b| b.is_ascii_digit())
+        && s.parse().is_ok_and(|n| range.contains(&n))
+}
+
+fn valid_time(time: &str) -> bool {
+    let parts: Vec<&str> = time.split(':').collect();
+    matches!(parts.as_slice(), [h, m, s] if h.len() == 2 && m.len() == 2 && s.len() == 2
+        && number(h, 0..=23) && number(m, 0..=59) && number(s, 0..=59))
+}
+
+/// `Mmm dd hh:mm:ss` with the day space padded, e.g. `Oct  1 22:14:15`
+pub(crate) fn valid_bsd_timestamp(timestamp: &str) -> bool {
+    if !timestamp.is_ascii() || timestamp.len() != 15 {
+        return false;
+    }
+    let (month, rest) = timestamp.split_at(3);
+    let day = match rest.as_bytes() {
+        [b' ', b' ', d, b' ', ..] if d.is_ascii_digit() && *d != b'0' => &rest[2..3],
+        [b' ', d, _, b' ', ..] if *d != b'0' => &rest[1..3],
+        _ => return false,
+    };
+    MONTHS.contains(&month) && number(day, 1..=31) && valid_time(&rest[4..])
+}
+
+/// RFC 5424 TIMESTAMP: `YYYY-MM-DDThh:mm:ss[.frac](Z|+hh:mm|-hh:mm)`
+pub(crate) fn valid_rfc3339(timestamp: &str) -> bool {
+    let Some((date, time)) = timestamp.split_once('T') else {
+        return false;
+    };
+    let date_ok = match date.split('-').collect::<Vec<_>>().as_slice() {
+        [y, m, d] => {
+            y.len() == 4
+                && m.len() == 2
+                && d.len() == 2
+                && number(y, 0..=9999)
+                && number(m, 1..=12)
+                && number(d, 1..=31)
+        }
+        _ => false,
+    };
+
+    let (time, offset) = match time.strip_suffix('Z') {
+        Some(time) => (time, None),
+        None => match time.rfind(['+', '-']) {
+            Some(index) => (&time[..index], Some(&time[index + 1..])),
+            None => return false,
+        },
+    };
+    let offset_ok = offset.is_none_or(|offset| {
+        matches!(offset.split_once(':'), Some((h, m)) if h.len() == 2 && m.len() == 2
+            && number(h, 0..=23) && number(m, 0..=59))
+    });
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "1"));
+    let fraction_ok =
+        (1..=6).contains(&fraction.len()) && fraction.bytes().all(|b| b.is_ascii_digit());
+
+    date_ok && offset_ok && fraction_ok && valid_time(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_compliant() {
+        assert!(check(
+            "<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - 'su root' failed"
+        )
+        .is_empty());
+        assert!(check(r#"<165>1 2003-08-24T05:14:15.000003-07:00 192.0.2.1 myproc 8710 - [exampleSDID@32473 iut="3" eventSource="App\]"][x@1 a="b"] msg"#).is_empty());
+        assert!(check("<0>1 - - - - - -").is_empty());
+        assert!(check("<13>Oct 11 22:14:15 mymachine su: su root").is_empty());
+        assert!(check("<13>Oct  1 22:14:15 mymachine su: su root").is_empty());
+    }
+
+    #[test]
+    fn test_check_pri() {
+        assert_eq!(check("no pri"), vec![Violation::MissingPri]);
+        assert_eq!(check("<13 1 - - - - - -"), vec![Violation::MissingPri]);
+        assert_eq!(check("<999>1 - - - - - -"), vec![Violation::PriOutOfRange]);
+        assert_eq!(check("<013>1 - - - - - -"), vec![Violation::PriLeadingZero]);
+    }
+
+    #[test]
+    fn test_check_version() {
+        assert_eq!(
+            check("<13>2003-10-11T22:14:15Z host app - - -"),
+            vec![Violation::MissingVersion]
+        );
+        assert_eq!(
+            check("<13>2 - host app - - -"),
+            vec![Violation::UnsupportedVersion]
+        );
+    }
+
+    #[test]
+    fn test_check_timestamps() {
+        for bad in [
+            "<13>1 2003-13-11T22:14:15Z host app - - -",
+            "<13>1 2003-10-11 22:14:15 host app - - -",
+            "<13>1 2003-10-11T25:14:15Z host app - - -",
+            "<13>1 2003-10-11T22:14:15 host app - - -",
+            "<13>1 2003-10-11T22:14:15.1234567Z host app - - -",
+            "<13>Oct 01 22:14:15 host su: x",
+            "<13>Oct 11 22:61:15 host su: x",
+            "<13>Foo 11 22:14:15 host su: x",
+        ] {
+            assert_eq!(check(bad), vec![Violation::BadTimestamp], "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_check_hostname_and_fields() {
+        assert_eq!(
+            check("<13>1 - web_01 app - - -"),
+            vec![Violation::InvalidHostnameChars]
+        );
+        assert_eq!(
+            check("<13>Oct 11 22:14:15 web/01 su: x"),
+            vec![Violation::InvalidHostnameChars]
+        );
+        let long_app = format!("<13>1 - host {} - - -", "a".repeat(49));
+        assert_eq!(check(&long_app), vec![Violation::AppNameTooLong]);
+        let long_msgid = format!("<13>1 - host app - {} -", "m".repeat(33));
+        assert_eq!(check(&long_msgid), vec![Violation::MsgIdTooLong]);
+        assert_eq!(
+            check("<13>1 - host app\u{7} - - -"),
+            vec![Violation::NonPrintableHeader]
+        );
+        assert_eq!(check("<13>1 - host app"), vec![Violation::TruncatedHeader]);
+
+        let long = format!("<13>Oct 11 22:14:15 host su: {}", "x".repeat(1024));
+        assert_eq!(check(&long), vec![Violation::MessageTooLong]);
+    }
+
+    #[test]
+    fn test_check_nilvalue() {
+        assert_eq!(check("<13>1 -  app - - -"), vec![Violation::NilvalueMisuse]);
+        assert_eq!(
+            check("<13>1 - host app - - -msg"),
+            vec![Violation::NilvalueMisuse]
+        );
+    }
+
+    #[test]
+    fn test_check_structured_data() {
+        assert_eq!(
+            check(r#"<13>1 - host app - - [bad=id a="b"] msg"#),
+            vec![Violation::IllegalSdName]
+        );
+        assert_eq!(
+            check(r#"<13>1 - host app - - [id a="b]"] msg"#),
+            vec![Violation::UnescapedSdValue]
+        );
+        assert_eq!(
+            check(r#"<13>1 - host app - - [id a=b] msg"#),
+            vec![Violation::MalformedStructuredData]
+        );
+        assert_eq!(
+            check("<13>1 - host app - - plain message"),
+            vec![Violation::MalformedStructuredData]
+        );
+    }
+}
@@ -1,4 +1,11 @@
+use crate::framing::SplitMode;
+use crate::grouping::{GroupKey, GroupOutput};
+use crate::hostname::{HostStep, HostsFile};
+use crate::rules::RuleSet;
+use crate::stats::DEFAULT_TOP_TEMPLATES;
+use crate::structured::normalize_pointer;
 use clap::Parser;
+use regex::Regex;
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser, Debug)]
@@ -17,6 +24,77 @@ pub struct Config {
     pub periodic: bool,
     #[arg(long, default_value_t = 5)]
     pub frequency: u64,
+    /// JSON pointer into structured payloads to break down per host (repeatable)
+    #[arg(long = "json-field", value_name = "POINTER", value_parser = normalize_pointer)]
+    pub json_fields: Vec<String>,
+    /// How to split datagrams that carry several messages
+    #[arg(long, value_enum, default_value_t = SplitMode::Auto)]
+    pub split: SplitMode,
+    /// Check messages against RFC 3164/5424 and report violations per host
+    #[arg(long, default_value_t = false)]
+    pub strict: bool,
+    /// Decode rsyslog `#012` and `\n`-style escapes of control characters in messages
+    #[arg(long, default_value_t = false)]
+    pub unescape: bool,
+    /// Hostname normalisation steps, applied in the given order (comma separated)
+    #[arg(
+        long = "normalize-host",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "STEPS"
+    )]
+    pub host_steps: Vec<HostStep>,
+    /// Hosts-style file (`ADDRESS NAME [ALIAS...]`) mapping addresses and aliases to names
+    #[arg(long, value_name = "PATH", value_parser = HostsFile::load)]
+    pub hosts_file: Option<HostsFile>,
+    /// JSON file of regex rules whose named captures are counted per host
+    #[arg(long, value_name = "PATH", value_parser = RuleSet::load)]
+    pub rules: Option<RuleSet>,
+    /// Number of message templates to report per host, 0 disables template mining
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_TOP_TEMPLATES)]
+    pub templates: usize,
+    /// Random samples to keep per host, besides its first message
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub samples: usize,
+    /// Keep `--samples` samples per host and severity instead
+    #[arg(long, default_value_t = false)]
+    pub samples_by_severity: bool,
+    /// Cut samples longer than this, marking how many bytes were left out
+    #[arg(long, value_name = "BYTES")]
+    pub max_sample_length: Option<usize>,
+    /// Track at most N hosts in detail, evicting the least active ones into an `other`
+    /// bucket, to bound memory when hostnames are spoofed
+    #[arg(long, value_name = "N")]
+    pub max_hosts: Option<usize>,
+    /// Count messages per combination of these keys (comma separated): host, source-ip,
+    /// app, facility, severity, vlan, collector or field:NAME
+    #[arg(
+        long = "group-by",
+        value_delimiter = ',',
+        value_name = "KEYS",
+        value_parser = GroupKey::parse
+    )]
+    pub group_by: Vec<GroupKey>,
+    /// Layout of the grouped counts in the summary
+    #[arg(long, value_enum, default_value_t = GroupOutput::Nested)]
+    pub group_output: GroupOutput,
+    /// Join events split across several messages, e.g. stack traces, before counting
+    #[arg(long, default_value_t = false)]
+    pub reassemble: bool,
+    /// Regex for the MSG of a line that continues the previous message (repeatable,
+    /// replaces the defaults)
+    #[arg(long = "continuation", value_name = "REGEX", value_parser = parse_regex)]
+    pub continuations: Vec<Regex>,
+    /// Milliseconds without a new line after which a reassembled event is complete
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub reassembly_timeout: u64,
+    /// Largest reassembled event in bytes, further lines start a new event
+    #[arg(long, value_name = "BYTES", default_value_t = 65536)]
+    pub reassembly_max_size: usize,
+}
+
+fn parse_regex(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid regex {}: {}", pattern, e))
 }
 
 impl Config {
@@ -41,6 +119,7 @@ pub fn determine_log_level(debug: bool, env_rust_log_is_set: bool) -> Option<log
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -93,6 +172,20 @@ mod tests {
         assert_eq!(config.interval, 10); // Default interval
         assert_eq!(config.periodic, false); // Default periodic
         assert_eq!(config.frequency, 5); // Default frequency
+        assert!(config.json_fields.is_empty());
+        assert_eq!(config.split, SplitMode::Auto);
+        assert!(!config.strict);
+        assert!(!config.unescape);
+        assert!(config.host_steps.is_empty());
+        assert!(config.hosts_file.is_none());
+        assert!(config.rules.is_none());
+        assert_eq!(config.templates, 5);
+        assert!(config.max_hosts.is_none());
+        assert_eq!(config.samples, 0);
+        assert!(!config.samples_by_severity);
+        assert!(config.max_sample_length.is_none());
+        assert!(config.group_by.is_empty());
+        assert_eq!(config.group_output, GroupOutput::Nested);
     }
 
     #[test]
@@ -109,6 +202,17 @@ mod tests {
             "--periodic",
             "--frequency",
             "15",
+            "--strict",
+            "--unescape",
+            "--templates",
+            "0",
+            "--max-hosts",
+            "1000",
+            "--samples",
+            "3",
+            "--samples-by-severity",
+            "--max-sample-length",
+            "256",
         ];
         let config = Config::parse_from(args);
 
@@ -118,5 +222,163 @@ mod tests {
         assert_eq!(config.interval, 20);
         assert_eq!(config.periodic, true);
         assert_eq!(config.frequency, 15);
+        assert!(config.strict);
+        assert!(config.unescape);
+        assert_eq!(config.templates, 0);
+        assert_eq!(config.max_hosts, Some(1000));
+        assert_eq!(config.samples, 3);
+        assert!(config.samples_by_severity);
+        assert_eq!(config.max_sample_length, Some(256));
+    }
+
+    #[test]
+    fn test_parse_json_fields() {
+        let args = vec![
+            "syslog_sniffer",
+            "--interface",
+            "eth0",
+            "--json-field",
+            "/user",
+            "--json-field",
+            "/event/outcome",
+            "--json-field",
+            "code",
+        ];
+        let config = Config::parse_from(args);
+        assert_eq!(config.json_fields, vec!["/user", "/event/outcome", "/code"]);
+
+        let args = vec!["syslog_sniffer", "--interface", "eth0", "--json-field", ""];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_split_mode() {
+        for (arg, mode) in [
+            ("none", SplitMode::None),
+            ("lf", SplitMode::Lf),
+            ("octet-counted", SplitMode::OctetCounted),
+            ("auto", SplitMode::Auto),
+        ] {
+            let args = vec!["syslog_sniffer", "--interface", "eth0", "--split", arg];
+            assert_eq!(Config::parse_from(args).split, mode);
+        }
+        let args = vec!["syslog_sniffer", "--interface", "eth0", "--split", "bogus"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_host_normalization() {
+        let path =
+            std::env::temp_dir().join(format!("syslog_sniffer_hosts_{}", std::process::id()));
+        std::fs::write(&path, "10.0.0.1 web01\n").unwrap();
+        let args = vec![
+            "syslog_sniffer",
+            "--interface",
+            "eth0",
+            "--normalize-host",
+            "lowercase,nilvalue,source-ip",
+            "--hosts-file",
+            path.to_str().unwrap(),
+        ];
+        let config = Config::parse_from(args);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            config.host_steps,
+            vec![HostStep::Lowercase, HostStep::Nilvalue, HostStep::SourceIp]
+        );
+        assert_eq!(
+            config.hosts_file.unwrap().resolve("10.0.0.1"),
+            Some("web01")
+        );
+
+        let args = vec![
+            "syslog_sniffer",
+            "-i",
+            "eth0",
+            "--hosts-file",
+            "/nonexistent",
+        ];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let path =
+            std::env::temp_dir().join(format!("syslog_sniffer_rules_{}", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"[{"name": "status", "pattern": "status=(?P<status>\\d+)"}]"#,
+        )
+        .unwrap();
+        let args = vec![
+            "syslog_sniffer",
+            "-i",
+            "eth0",
+            "--rules",
+            path.to_str().unwrap(),
+        ];
+        let result = Config::try_parse_from(args);
+        std::fs::remove_file(&path).unwrap();
+        assert!(!result.unwrap().rules.unwrap().is_empty());
+
+        let args = vec!["syslog_sniffer", "-i", "eth0", "--rules", "/nonexistent"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_grouping() {
+        let config = Config::parse_from([
+            "syslog_sniffer",
+            "-i",
+            "eth0",
+            "--group-by",
+            "host,severity,field:/user",
+            "--group-output",
+            "flat",
+        ]);
+        assert_eq!(
+            config.group_by,
+            vec![
+                GroupKey::Host,
+                GroupKey::Severity,
+                GroupKey::Field("/user".to_string())
+            ]
+        );
+        assert_eq!(config.group_output, GroupOutput::Flat);
+
+        let args = ["syslog_sniffer", "-i", "eth0", "--group-by", "host,bogus"];
+        assert!(Config::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_reassembly() {
+        let config = Config::parse_from(["syslog_sniffer", "-i", "eth0"]);
+        assert!(!config.reassemble);
+        assert!(config.continuations.is_empty());
+        assert_eq!(config.reassembly_timeout, 1000);
+        assert_eq!(config.reassembly_max_size, 65536);
+
+        let config = Config::parse_from([
+            "syslog_sniffer",
+            "-i",
+            "eth0",
+            "--reassemble",
+            "--continuation",
+            r"^\s+at ",
+            "--continuation",
+            "^Caused by:",
+            "--reassembly-timeout",
+            "250",
+            "--reassembly-max-size",
+            "4096",
+        ]);
+        assert!(config.reassemble);
+        assert_eq!(config.continuations.len(), 2);
+        assert!(config.continuations[0].is_match("    at Main.run"));
+        assert_eq!(config.reassembly_timeout, 250);
+        assert_eq!(config.reassembly_max_size, 4096);
+
+        let args = ["syslog_sniffer", "-i", "eth0", "--continuation", "("];
+        assert!(Config::try_parse_from(args).is_err());
     }
 }
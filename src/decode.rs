@@ -0,0 +1,191 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const IPPROTO_UDP: u8 = 17;
+
+/// A UDP datagram decoded from a captured Ethernet frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpDatagram<'a> {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// Innermost 802.1Q VLAN id, if the frame was tagged
+    pub vlan: Option<u16>,
+    pub payload: &'a [u8],
+}
+
+/// Source address, destination address and the transport payload of an IP packet
+type IpPayload<'a> = (IpAddr, IpAddr, &'a [u8]);
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Decodes an Ethernet II frame (optionally VLAN tagged) carrying IPv4 or IPv6 UDP.
+///
+/// Returns `None` for anything else, including non-first IPv4 fragments.
+pub fn decode_udp(frame: &[u8]) -> Option<UdpDatagram<'_>> {
+    let mut offset = 12;
+    let mut vlan = None;
+    let mut ethertype = read_u16(frame, offset)?;
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        vlan = Some(read_u16(frame, offset + 2)? & 0x0fff);
+        offset += 4;
+        ethertype = read_u16(frame, offset)?;
+    }
+    offset += 2;
+
+    let (src_ip, dst_ip, udp) = match ethertype {
+        ETHERTYPE_IPV4 => decode_ipv4(frame.get(offset..)?)?,
+        ETHERTYPE_IPV6 => decode_ipv6(frame.get(offset..)?)?,
+        _ => return None,
+    };
+
+    let src_port = read_u16(udp, 0)?;
+    let dst_port = read_u16(udp, 2)?;
+    let length = usize::from(read_u16(udp, 4)?);
+    if length < 8 {
+        return None;
+    }
+    // Trust the UDP length over the capture length, frames may carry padding
+    let payload = udp.get(8..length.min(udp.len()))?;
+
+    Some(UdpDatagram {
+        src_ip,
+        dst_ip,
+        src_port,
+        dst_port,
+        vlan,
+        payload,
+    })
+}
+
+fn decode_ipv4(packet: &[u8]) -> Option<IpPayload<'_>> {
+    let version_ihl = *packet.first()?;
+    if version_ihl >> 4 != 4 {
+        return None;
+    }
+    let header_len = usize::from(version_ihl & 0x0f) * 4;
+    let total_len = usize::from(read_u16(packet, 2)?);
+    let fragment_offset = read_u16(packet, 6)? & 0x1fff;
+    if *packet.get(9)? != IPPROTO_UDP || fragment_offset != 0 || header_len < 20 {
+        return None;
+    }
+
+    let src: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+    let end = total_len.clamp(header_len, packet.len());
+    Some((
+        IpAddr::V4(Ipv4Addr::from(src)),
+        IpAddr::V4(Ipv4Addr::from(dst)),
+        packet.get(header_len..end)?,
+    ))
+}
+
+fn decode_ipv6(packet: &[u8]) -> Option<IpPayload<'_>> {
+    if *packet.first()? >> 4 != 6 {
+        return None;
+    }
+    // Extension headers are not followed, syslog senders don't use them
+    if *packet.get(6)? != IPPROTO_UDP {
+        return None;
+    }
+
+    let src: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+    Some((
+        IpAddr::V6(Ipv6Addr::from(src)),
+        IpAddr::V6(Ipv6Addr::from(dst)),
+        packet.get(40..)?,
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds an Ethernet/IPv4/UDP frame around `payload`, optionally VLAN tagged.
+    pub(crate) fn udp_frame(
+        src: [u8; 4],
+        dst: [u8; 4],
+        vlan: Option<u16>,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        if let Some(id) = vlan {
+            frame.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            frame.extend_from_slice(&id.to_be_bytes());
+        }
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+        let total_len = (20 + 8 + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&514u16.to_be_bytes());
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_decode_ipv4_udp() {
+        let frame = udp_frame([10, 0, 0, 1], [10, 0, 0, 2], None, b"<13>hello");
+        let datagram = decode_udp(&frame).unwrap();
+        assert_eq!(datagram.src_ip, IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(datagram.dst_ip, IpAddr::from([10, 0, 0, 2]));
+        assert_eq!(datagram.src_port, 40000);
+        assert_eq!(datagram.dst_port, 514);
+        assert_eq!(datagram.vlan, None);
+        assert_eq!(datagram.payload, b"<13>hello");
+    }
+
+    #[test]
+    fn test_decode_vlan_and_padding() {
+        let mut frame = udp_frame([10, 0, 0, 1], [10, 0, 0, 2], Some(42), b"hi");
+        frame.extend_from_slice(&[0; 16]); // Ethernet minimum frame padding
+        let datagram = decode_udp(&frame).unwrap();
+        assert_eq!(datagram.vlan, Some(42));
+        assert_eq!(datagram.payload, b"hi");
+    }
+
+    #[test]
+    fn test_decode_ipv6_udp() {
+        let payload = b"<13>v6";
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[IPPROTO_UDP, 64]);
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&[0x9c, 0x40, 0x02, 0x02]);
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+
+        let datagram = decode_udp(&frame).unwrap();
+        assert_eq!(datagram.src_ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(datagram.payload, payload);
+    }
+
+    #[test]
+    fn test_decode_not_udp() {
+        assert!(decode_udp(b"<13>Oct 11 22:14:15 mymachine su: su root").is_none());
+        assert!(decode_udp(&[]).is_none());
+
+        let mut frame = udp_frame([10, 0, 0, 1], [10, 0, 0, 2], None, b"x");
+        frame[14 + 9] = 6; // TCP
+        assert!(decode_udp(&frame).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// How control characters such as newlines are encoded in a message, which hints
+/// at the relay configuration that produced it.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Escape {
+    /// rsyslog's `#ooo` octal escapes, e.g. `#012` for a newline
+    Octal,
+    /// C-style `\n`, `\r` and `\t`
+    Backslash,
+    /// Control characters that weren't escaped at all
+    RawControl,
+}
+
+fn is_control(byte: u8) -> bool {
+    byte < 0x20 || byte == 0x7f
+}
+
+/// The control character, or the `#` of an escaped `#043`, a `#ooo` sequence at the
+/// start of `bytes` encodes. Other octal values are left alone, `#100` is more likely
+/// a ticket than an `@`.
+fn octal_escape(bytes: &[u8]) -> Option<u8> {
+    let digits = bytes.get(1..4)?;
+    if bytes[0] != b'#' || !digits.iter().all(|b| (b'0'..=b'7').contains(b)) {
+        return None;
+    }
+    let value = digits
+        .iter()
+        .fold(0u16, |value, digit| value * 8 + u16::from(digit - b'0'));
+    u8::try_from(value)
+        .ok()
+        .filter(|&byte| is_control(byte) || byte == b'#')
+}
+
+/// The byte a `\n`-style escape at the start of `bytes` encodes, `\\` included.
+fn backslash_escape(bytes: &[u8]) -> Option<u8> {
+    match (bytes.first()?, bytes.get(1)?) {
+        (b'\\', b'\\') => Some(b'\\'),
+        (b'\\', b'n') => Some(b'\n'),
+        (b'\\', b'r') => Some(b'\r'),
+        (b'\\', b't') => Some(b'\t'),
+        _ => None,
+    }
+}
+
+/// Whether every backslash in `bytes` starts a `\n`-style escape. Otherwise they are
+/// likely path separators, as in `C:\Windows\temp\new.txt`, and none is decoded.
+fn backslashes_are_escapes(bytes: &[u8]) -> bool {
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] != b'\\' {
+            index += 1;
+        } else if backslash_escape(&bytes[index..]).is_some() {
+            index += 2;
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+/// Lists the encodings of control characters found in `msg`, in declaration order.
+pub fn detect(msg: &str) -> Vec<Escape> {
+    let bytes = msg.as_bytes();
+    let escaped_backslashes = backslashes_are_escapes(bytes);
+    let (mut octal, mut backslash, mut raw) = (false, false, false);
+    let mut index = 0;
+    while index < bytes.len() {
+        let rest = &bytes[index..];
+        match rest[0] {
+            b'#' => octal |= octal_escape(rest).is_some(),
+            // An escaped backslash isn't a control character, and can't start an escape
+            b'\\' if !escaped_backslashes => {}
+            b'\\' => match backslash_escape(rest) {
+                Some(b'\\') => index += 1,
+                escape => backslash |= escape.is_some(),
+            },
+            byte => raw |= is_control(byte),
+        }
+        index += 1;
+    }
+
+    [
+        (octal, Escape::Octal),
+        (backslash, Escape::Backslash),
+        (raw, Escape::RawControl),
+    ]
+    .into_iter()
+    .filter_map(|(found, escape)| found.then_some(escape))
+    .collect()
+}
+
+/// Encodes control characters as rsyslog does, e.g. a newline as `#012`, so a
+/// message fits on one line. Text [`unescape`] would decode is escaped as well, a
+/// literal `#012` as `#043012` and, if every backslash starts an escape, a
+/// backslash as `\\`, so unescaping gives back `text`.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let double_backslashes = bytes.contains(&b'\\') && backslashes_are_escapes(bytes);
+    let literal_octal =
+        |index: usize| bytes[index] == b'#' && octal_escape(&bytes[index..]).is_some();
+    if !double_backslashes
+        && !(0..bytes.len()).any(|index| is_control(bytes[index]) || literal_octal(index))
+    {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 8);
+    for (index, c) in text.char_indices() {
+        match c {
+            '\\' if double_backslashes => out.push_str("\\\\"),
+            '#' if literal_octal(index) => out.push_str("#043"),
+            c if c.is_ascii() && is_control(c as u8) => {
+                out.push_str(&format!("#{:03o}", c as u8));
+            }
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Encodes control characters only, for text whose other escapes belong to it,
+/// such as a JSON payload.
+pub fn escape_controls(text: &str) -> Cow<'_, str> {
+    if !text.bytes().any(is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if c.is_ascii() && is_control(c as u8) {
+            out.push_str(&format!("#{:03o}", c as u8));
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Decodes `#ooo` and `\n`-style escapes of control characters, and `\\`. Backslash
+/// escapes are only decoded if every backslash in `text` is part of one.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let escaped_backslashes = backslashes_are_escapes(bytes);
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        let rest = &bytes[index..];
+        let (decoded, len) = match octal_escape(rest) {
+            Some(byte) => (byte, 4),
+            None => match backslash_escape(rest).filter(|_| escaped_backslashes) {
+                Some(byte) => (byte, 2),
+                None => {
+                    index += 1;
+                    continue;
+                }
+            },
+        };
+        // Escapes are ASCII, so these are always character boundaries
+        out.push_str(&text[copied..index]);
+        out.push(char::from(decoded));
+        index += len;
+        copied = index;
+    }
+
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(&text[copied..]);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("plain message, issue #100"), vec![]);
+        assert_eq!(
+            detect("java.lang.Error#012#011at Main.run"),
+            vec![Escape::Octal]
+        );
+        assert_eq!(detect(r"line one\nline two"), vec![Escape::Backslash]);
+        assert_eq!(
+            detect("tab\tand\\t"),
+            vec![Escape::Backslash, Escape::RawControl]
+        );
+        assert_eq!(detect("bell\x07 #01"), vec![Escape::RawControl]);
+        assert_eq!(detect(r"open C:\\new\\temp failed"), vec![]);
+        assert_eq!(detect(r"a\\\nb"), vec![Escape::Backslash]);
+        assert_eq!(detect(r"opened C:\Windows\temp\new.txt"), vec![]);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert!(matches!(
+            unescape("nothing #100 \\d"),
+            Cow::Borrowed("nothing #100 \\d")
+        ));
+        assert_eq!(
+            unescape("Error#012#011at Main.run(Main.java:5)"),
+            "Error\n\tat Main.run(Main.java:5)"
+        );
+        assert_eq!(unescape(r"a\nb\r\tc"), "a\nb\r\tc");
+        // A lone backslash isn't an escape, so neither are the others
+        assert_eq!(unescape(r"a\nb\r\tc\"), r"a\nb\r\tc\");
+        assert_eq!(unescape("ünïcode#012"), "ünïcode\n");
+        assert_eq!(unescape("#01"), "#01");
+        assert_eq!(unescape(r"a\\nb"), r"a\nb");
+        assert_eq!(
+            unescape(r"open C:\\new\\temp failed\nretrying"),
+            "open C:\\new\\temp failed\nretrying"
+        );
+        assert_eq!(
+            unescape(r"opened C:\Windows\temp\new.txt#012"),
+            "opened C:\\Windows\\temp\\new.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(matches!(escape("plain #100"), Cow::Borrowed("plain #100")));
+        assert_eq!(escape("a\nb\tc\x7fé"), "a#012b#011c#177é");
+        assert_eq!(unescape(&escape("a\r\nb")), "a\r\nb");
+
+        // Text that reads as an escape is escaped too, so it survives unescaping
+        assert_eq!(escape("#012 ##043"), "#043012 ##043043");
+        assert_eq!(escape(r"a\nb\\"), r"a\\nb\\\\");
+        assert_eq!(escape_controls("#012\n"), "#012#012");
+        assert!(matches!(escape(r"C:\Windows\new"), Cow::Borrowed(_)));
+        for text in ["#012\n", r"\n#043", "C:\\new\n#012", "#0#012"] {
+            assert_eq!(unescape(&escape(text)), text);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::auth::{parse_auth, AuthEvent};
+use crate::windows::{parse_windows, WindowsEvent};
+use serde::Serialize;
+use serde_json::Value;
+
+/// A well-known payload recognised in the MSG.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// iptables/nftables `LOG` target output
+    Netfilter(NetfilterEvent),
+    /// An auditd record, e.g. forwarded by audisp-syslog
+    Audit(AuditEvent),
+    /// An sshd login, sudo invocation or PAM failure
+    Auth(AuthEvent),
+    /// A Windows event log record forwarded by Snare or NXLog
+    Windows(WindowsEvent),
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct NetfilterEvent {
+    /// The rule's log prefix, e.g. `[UFW BLOCK]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// False only when the prefix says the packet was accepted or allowed
+    pub blocked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_interface: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub out_interface: Option<String>,
+    pub src: String,
+    pub dst: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dst_port: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AuditEvent {
+    /// e.g. `SYSCALL`, `USER_LOGIN` or `AVC`
+    pub record_type: String,
+    /// Seconds since the epoch, as written in `msg=audit(...)`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<u64>,
+    /// `key=value` pairs with quotes removed. The fields of a nested
+    /// `msg='...'` are flattened into the list.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Recognises netfilter, auditd, authentication and Windows event log payloads.
+/// `structured` is the message's JSON payload, if any. Messages without the formats'
+/// markers are rejected with substring searches only, so ordinary traffic costs no
+/// allocation.
+pub fn parse_event(msg: &str, structured: Option<&Value>) -> Option<Event> {
+    parse_windows(msg, structured)
+        .map(Event::Windows)
+        .or_else(|| parse_netfilter(msg).map(Event::Netfilter))
+        .or_else(|| parse_audit(msg).map(Event::Audit))
+        .or_else(|| parse_auth(msg).map(Event::Auth))
+}
+
+/// Finds `needle` at the start of a `key=value` token.
+pub(crate) fn find_token(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .match_indices(needle)
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || matches!(haystack.as_bytes()[index - 1], b' ' | b']'))
+}
+
+/// Splits `key=value` tokens, where values may be quoted with `"` or `'`.
+/// Tokens without `=`, such as netfilter's `DF` or `SYN` flags, are skipped.
+pub(crate) fn key_values(input: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = input;
+    std::iter::from_fn(move || loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let token_end = rest.find([' ', '=']).unwrap_or(rest.len());
+        let (key, after) = rest.split_at(token_end);
+        let Some(after) = after.strip_prefix('=') else {
+            rest = after;
+            continue;
+        };
+
+        let (value, remainder) = match after.chars().next() {
+            Some(quote @ ('"' | '\'')) => match after[1..].find(quote) {
+                Some(end) => (&after[1..end + 1], &after[end + 2..]),
+                None => (&after[1..], ""),
+            },
+            _ => after.split_at(after.find(' ').unwrap_or(after.len())),
+        };
+        rest = remainder;
+        return Some((key, value));
+    })
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// `[  123.456789] ` as printed by the kernel in front of its messages
+fn strip_kernel_timestamp(msg: &str) -> &str {
+    msg.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .filter(|(stamp, _)| {
+            let stamp = stamp.trim();
+            !stamp.is_empty() && stamp.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        })
+        .map_or(msg, |(_, rest)| rest.trim_start())
+}
+
+fn parse_netfilter(msg: &str) -> Option<NetfilterEvent> {
+    let start = find_token(msg, "IN=")?;
+    if !msg[start..].contains(" OUT=") {
+        return None;
+    }
+
+    let prefix = strip_kernel_timestamp(&msg[..start]).trim();
+    let upper = prefix.to_ascii_uppercase();
+    let mut event = NetfilterEvent {
+        prefix: non_empty(prefix),
+        blocked: !(upper.contains("ACCEPT") || upper.contains("ALLOW")),
+        in_interface: None,
+        out_interface: None,
+        src: String::new(),
+        dst: String::new(),
+        protocol: None,
+        src_port: None,
+        dst_port: None,
+    };
+    for (key, value) in key_values(&msg[start..]) {
+        match key {
+            "IN" => event.in_interface = non_empty(value),
+            "OUT" => event.out_interface = non_empty(value),
+            "SRC" => event.src = value.to_string(),
+            "DST" => event.dst = value.to_string(),
+            "PROTO" => event.protocol = non_empty(value),
+            "SPT" => event.src_port = value.parse().ok(),
+            "DPT" => event.dst_port = value.parse().ok(),
+            _ => {}
+        }
+    }
+    (!event.src.is_empty() && !event.dst.is_empty()).then_some(event)
+}
+
+fn parse_audit(msg: &str) -> Option<AuditEvent> {
+    let start = find_token(msg, "type=")?;
+    let (record_type, rest) = msg[start + "type=".len()..].split_once(' ')?;
+    let (stamp, rest) = rest
+        .trim_start()
+        .strip_prefix("msg=audit(")?
+        .split_once("):")?;
+    let (timestamp, serial) = match stamp.split_once(':') {
+        Some((timestamp, serial)) => (timestamp, serial.parse().ok()),
+        None => (stamp, None),
+    };
+
+    let mut fields = Vec::new();
+    for (key, value) in key_values(rest) {
+        // USER_* records carry their details in a quoted msg='...'
+        if key == "msg" && value.contains('=') {
+            fields.extend(key_values(value).map(|(k, v)| (k.to_string(), v.to_string())));
+        } else {
+            fields.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    Some(AuditEvent {
+        record_type: record_type.to_string(),
+        timestamp: non_empty(timestamp),
+        serial,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(msg: &str) -> Option<Event> {
+        parse_event(msg, None)
+    }
+
+    #[test]
+    fn test_parse_netfilter() {
+        let msg = "[ 5123.456789] [UFW BLOCK] IN=eth0 OUT= MAC=00:11:22 SRC=203.0.113.5 \
+                   DST=10.0.0.1 LEN=60 TTL=50 DF PROTO=TCP SPT=51000 DPT=22 SYN URGP=0";
+        let Some(Event::Netfilter(event)) = parse(msg) else {
+            panic!("not recognised: {msg}");
+        };
+        assert_eq!(event.prefix.as_deref(), Some("[UFW BLOCK]"));
+        assert!(event.blocked);
+        assert_eq!(event.in_interface.as_deref(), Some("eth0"));
+        assert_eq!(event.out_interface, None);
+        assert_eq!(event.src, "203.0.113.5");
+        assert_eq!(event.dst, "10.0.0.1");
+        assert_eq!(event.protocol.as_deref(), Some("TCP"));
+        assert_eq!(event.src_port, Some(51000));
+        assert_eq!(event.dst_port, Some(22));
+
+        let msg = "nft accept: IN=eth0 OUT=eth1 SRC=10.0.0.2 DST=10.0.1.2 PROTO=ICMP TYPE=8";
+        let Some(Event::Netfilter(event)) = parse(msg) else {
+            panic!("not recognised: {msg}");
+        };
+        assert!(!event.blocked);
+        assert_eq!(event.dst_port, None);
+
+        assert_eq!(parse("LOGIN=alice OUT= SRC=1.2.3.4 DST=5.6.7.8"), None);
+        assert_eq!(parse("IN=eth0 OUT= SRC=1.2.3.4"), None);
+    }
+
+    #[test]
+    fn test_parse_audit() {
+        let msg = r#"node=web01 type=USER_LOGIN msg=audit(1364481363.243:24287): pid=3170 uid=0 msg='op=login acct="root" exe="/usr/sbin/sshd" addr=203.0.113.5 res=failed'"#;
+        let Some(Event::Audit(event)) = parse(msg) else {
+            panic!("not recognised: {msg}");
+        };
+        assert_eq!(event.record_type, "USER_LOGIN");
+        assert_eq!(event.timestamp.as_deref(), Some("1364481363.243"));
+        assert_eq!(event.serial, Some(24287));
+        let field = |name: &str| {
+            event
+                .fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(field("pid"), Some("3170"));
+        assert_eq!(field("acct"), Some("root"));
+        assert_eq!(field("exe"), Some("/usr/sbin/sshd"));
+        assert_eq!(field("res"), Some("failed"));
+        assert_eq!(field("msg"), None);
+
+        let msg = "type=AVC msg=audit(1.5): avc:  denied";
+        let Some(Event::Audit(event)) = parse(msg) else {
+            panic!("not recognised: {msg}");
+        };
+        assert_eq!(event.serial, None);
+
+        assert_eq!(parse("subtype=x msg=audit(1:2): a=b"), None);
+        assert_eq!(parse("type=SYSCALL no audit stamp"), None);
+        assert_eq!(parse("su root"), None);
+    }
+
+    #[test]
+    fn test_key_values() {
+        let pairs: Vec<_> = key_values(r#"a=1  FLAG b="x y" c='q' d= e="open"#).collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("a", "1"),
+                ("b", "x y"),
+                ("c", "q"),
+                ("d", ""),
+                ("e", "open")
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use clap::ValueEnum;
+
+/// How a single datagram is split into syslog messages.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitMode {
+    /// The whole datagram is one message
+    None,
+    /// One message per line, empty lines are skipped
+    Lf,
+    /// RFC 6587 octet counting: `LEN SP MSG`, repeated
+    OctetCounted,
+    /// Octet counting if the datagram starts with a length prefix,
+    /// LF splitting if every line starts with a `<PRI>`, otherwise none
+    #[default]
+    Auto,
+}
+
+/// Iterator over the messages packed into one datagram.
+pub struct Messages<'a> {
+    rest: &'a [u8],
+    mode: SplitMode,
+}
+
+/// Splits a datagram into messages according to `mode`.
+pub fn split_messages(payload: &[u8], mode: SplitMode) -> Messages<'_> {
+    let mode = match mode {
+        SplitMode::Auto => detect_mode(payload),
+        mode => mode,
+    };
+    Messages {
+        rest: payload,
+        mode,
+    }
+}
+
+fn detect_mode(payload: &[u8]) -> SplitMode {
+    // The frame must hold a PRI, or a PRI-less "3 apples..." would be split
+    let octet_counted =
+        octet_count(payload).is_some_and(|(_, prefix)| payload.get(prefix) == Some(&b'<'));
+    if octet_counted {
+        SplitMode::OctetCounted
+    } else if payload.contains(&b'\n') && lines(payload).all(|line| line.starts_with(b"<")) {
+        SplitMode::Lf
+    } else {
+        SplitMode::None
+    }
+}
+
+fn lines(payload: &[u8]) -> impl Iterator<Item = &[u8]> {
+    payload
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+}
+
+/// Parses a `LEN SP` prefix, returning the length and the prefix size.
+fn octet_count(data: &[u8]) -> Option<(usize, usize)> {
+    let digits = data.iter().take_while(|b| b.is_ascii_digit()).count();
+    // RFC 6587 forbids a leading zero and caps messages well below 10 digits
+    if digits == 0 || digits > 9 || data[0] == b'0' || data.get(digits) != Some(&b' ') {
+        return None;
+    }
+    let len = std::str::from_utf8(&data[..digits]).ok()?.parse().ok()?;
+    Some((len, digits + 1))
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.mode {
+            SplitMode::None | SplitMode::Auto => {
+                let message = std::mem::take(&mut self.rest);
+                (!message.is_empty()).then_some(message)
+            }
+            SplitMode::Lf => loop {
+                if self.rest.is_empty() {
+                    return None;
+                }
+                let (line, rest) = match self.rest.iter().position(|&b| b == b'\n') {
+                    Some(index) => (&self.rest[..index], &self.rest[index + 1..]),
+                    None => (self.rest, &[][..]),
+                };
+                self.rest = rest;
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                if !line.is_empty() {
+                    return Some(line);
+                }
+            },
+            SplitMode::OctetCounted => {
+                self.rest = self.rest.trim_ascii_start();
+                if self.rest.is_empty() {
+                    return None;
+                }
+                match octet_count(self.rest) {
+                    Some((len, prefix)) if prefix + len <= self.rest.len() => {
+                        let message = &self.rest[prefix..prefix + len];
+                        self.rest = &self.rest[prefix + len..];
+                        Some(message)
+                    }
+                    // Truncated or unframed remainder: hand it over as-is
+                    _ => Some(std::mem::take(&mut self.rest)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(payload: &str, mode: SplitMode) -> Vec<&str> {
+        split_messages(payload.as_bytes(), mode)
+            .map(|m| std::str::from_utf8(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_none() {
+        assert_eq!(split("<13>a\n<13>b", SplitMode::None), vec!["<13>a\n<13>b"]);
+        assert!(split("", SplitMode::None).is_empty());
+    }
+
+    #[test]
+    fn test_split_lf() {
+        assert_eq!(
+            split("<13>a\r\n\n<13>b\n", SplitMode::Lf),
+            vec!["<13>a", "<13>b"]
+        );
+        assert_eq!(split("no newline", SplitMode::Lf), vec!["no newline"]);
+    }
+
+    #[test]
+    fn test_split_octet_counted() {
+        assert_eq!(
+            split("5 <13>a 6 <13>bc\n", SplitMode::OctetCounted),
+            vec!["<13>a", "<13>bc"]
+        );
+        // Length overruns the datagram: the rest is one message
+        assert_eq!(
+            split("5 <13>a 60 <13>bc", SplitMode::OctetCounted),
+            vec!["<13>a", "60 <13>bc"]
+        );
+    }
+
+    #[test]
+    fn test_split_auto() {
+        assert_eq!(
+            split("5 <13>a5 <13>b", SplitMode::Auto),
+            vec!["<13>a", "<13>b"]
+        );
+        assert_eq!(
+            split("<13>a\n<14>b\n", SplitMode::Auto),
+            vec!["<13>a", "<14>b"]
+        );
+        // Continuation lines keep a multi-line message together
+        assert_eq!(
+            split("<13>java.lang.Exception\n\tat Foo.bar", SplitMode::Auto),
+            vec!["<13>java.lang.Exception\n\tat Foo.bar"]
+        );
+        assert_eq!(split("05 <13>a", SplitMode::Auto), vec!["05 <13>a"]);
+        assert_eq!(
+            split("3 apples were sold", SplitMode::Auto),
+            vec!["3 apples were sold"]
+        );
+    }
+}
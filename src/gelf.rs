@@ -0,0 +1,318 @@
+use crate::{MessageFormat, OwnedSyslogPacket, SyslogPacket};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const CHUNK_HEADER_LEN: usize = 12;
+/// Limits from the GELF specification
+const MAX_CHUNKS: u8 = 128;
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Upper bound for a decompressed message, guards against zip bombs
+const MAX_MESSAGE_SIZE: u64 = 8 * 1024 * 1024;
+const MAX_PENDING_MESSAGES: usize = 1024;
+/// Bounds the chunks buffered for reassembly, which spoofed UDP could otherwise fill
+/// with gigabytes before the timeout
+const MAX_PENDING_BYTES: usize = 32 * 1024 * 1024;
+
+/// Returns true if the UDP payload looks like GELF (chunked, compressed or plain JSON).
+pub fn is_gelf(payload: &[u8]) -> bool {
+    match payload {
+        [0x1e, 0x0f, ..] | [0x1f, 0x8b, ..] => true,
+        // zlib header: deflate method and a header checksum divisible by 31
+        [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => {
+            true
+        }
+        [b'{', ..] => true,
+        _ => false,
+    }
+}
+
+/// Returns true for a chunk of a chunked GELF message, which is never plain syslog.
+pub fn is_chunk(payload: &[u8]) -> bool {
+    payload.starts_with(&CHUNK_MAGIC)
+}
+
+struct PendingMessage {
+    first_seen: Instant,
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    bytes: usize,
+}
+
+/// Decodes GELF datagrams, reassembling chunked messages across packets.
+pub struct GelfDecoder {
+    pending: HashMap<[u8; 8], PendingMessage>,
+    /// Chunk data buffered over all pending messages
+    pending_bytes: usize,
+    max_pending_bytes: usize,
+}
+
+impl Default for GelfDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GelfDecoder {
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+            pending_bytes: 0,
+            max_pending_bytes: MAX_PENDING_BYTES,
+        }
+    }
+
+    /// Feeds one UDP payload. Returns a packet once a complete message is available.
+    pub fn decode(&mut self, payload: &[u8], now: Instant) -> Option<OwnedSyslogPacket> {
+        self.expire(now);
+
+        if is_chunk(payload) {
+            let message = self.add_chunk(payload, now)?;
+            return decode_message(&message);
+        }
+        decode_message(payload)
+    }
+
+    fn add_chunk(&mut self, payload: &[u8], now: Instant) -> Option<Vec<u8>> {
+        if payload.len() < CHUNK_HEADER_LEN {
+            return None;
+        }
+        let id: [u8; 8] = payload[2..10].try_into().ok()?;
+        let sequence = payload[10];
+        let count = payload[11];
+        if count == 0 || count > MAX_CHUNKS || sequence >= count {
+            return None;
+        }
+        if !self.pending.contains_key(&id) && self.pending.len() >= MAX_PENDING_MESSAGES {
+            return None;
+        }
+        // New chunks are refused while the buffer is full, until messages complete or expire
+        let data = &payload[CHUNK_HEADER_LEN..];
+        if self.pending_bytes + data.len() > self.max_pending_bytes {
+            return None;
+        }
+
+        let pending = self.pending.entry(id).or_insert_with(|| PendingMessage {
+            first_seen: now,
+            chunks: vec![None; usize::from(count)],
+            received: 0,
+            bytes: 0,
+        });
+        // A sender reusing an id with a different count is broken, drop what we have
+        if pending.chunks.len() != usize::from(count) {
+            self.remove(&id);
+            return None;
+        }
+
+        let slot = &mut pending.chunks[usize::from(sequence)];
+        if slot.is_none() {
+            *slot = Some(data.to_vec());
+            pending.received += 1;
+            pending.bytes += data.len();
+            self.pending_bytes += data.len();
+        }
+        if pending.received < pending.chunks.len() {
+            return None;
+        }
+
+        let pending = self.remove(&id)?;
+        Some(pending.chunks.into_iter().flatten().flatten().collect())
+    }
+
+    fn remove(&mut self, id: &[u8; 8]) -> Option<PendingMessage> {
+        let pending = self.pending.remove(id)?;
+        self.pending_bytes -= pending.bytes;
+        Some(pending)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let pending_bytes = &mut self.pending_bytes;
+        self.pending.retain(|_, pending| {
+            let keep = now.duration_since(pending.first_seen) < CHUNK_TIMEOUT;
+            if !keep {
+                *pending_bytes -= pending.bytes;
+            }
+            keep
+        });
+    }
+}
+
+fn decompress<R: Read>(reader: R) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader
+        .take(MAX_MESSAGE_SIZE)
+        .read_to_end(&mut buffer)
+        .ok()?;
+    Some(buffer)
+}
+
+fn decode_message(data: &[u8]) -> Option<OwnedSyslogPacket> {
+    let json = if data.starts_with(&GZIP_MAGIC) {
+        decompress(GzDecoder::new(data))?
+    } else if data.first() == Some(&b'{') {
+        data.to_vec()
+    } else {
+        decompress(ZlibDecoder::new(data))?
+    };
+
+    let value: Value = serde_json::from_slice(&json).ok()?;
+    // The decoded JSON is what collectors store and bill for
+    let message = String::from_utf8(json).ok()?;
+    let short_message = value.get("short_message")?.as_str()?.to_string();
+    let hostname = value
+        .get("host")
+        .and_then(Value::as_str)
+        .map(|host| Cow::Owned(host.to_string()));
+    let severity = value
+        .get("level")
+        .and_then(Value::as_u64)
+        .filter(|level| *level <= 7)
+        .map(|level| level as u8);
+
+    Some(SyslogPacket {
+        size: message.len(),
+        message: Cow::Owned(message),
+        timestamp: None,
+        hostname,
+        app_name: None,
+        procid: None,
+        msgid: None,
+        structured_data: None,
+        msg: Cow::Owned(short_message),
+        structured: Some(value),
+        extracted: Vec::new(),
+        event: None,
+        escapes: Vec::new(),
+        format: MessageFormat::Gelf,
+        facility: None,
+        severity,
+        violations: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    const MESSAGE: &str =
+        r#"{"version":"1.1","host":"app01","short_message":"disk full","level":3,"_app":"db"}"#;
+
+    fn chunk(id: u8, sequence: u8, count: u8, data: &[u8]) -> Vec<u8> {
+        let mut chunk = CHUNK_MAGIC.to_vec();
+        chunk.extend_from_slice(&[id; 8]);
+        chunk.extend_from_slice(&[sequence, count]);
+        chunk.extend_from_slice(data);
+        chunk
+    }
+
+    #[test]
+    fn test_is_gelf() {
+        assert!(is_gelf(MESSAGE.as_bytes()));
+        assert!(is_gelf(&chunk(1, 0, 1, b"{}")));
+        assert!(is_gelf(&[0x1f, 0x8b, 0x08]));
+        assert!(is_gelf(&[0x78, 0x9c]));
+        assert!(!is_gelf(b"<13>Oct 11 22:14:15 mymachine su: su root"));
+        assert!(!is_gelf(&[]));
+        assert!(is_chunk(&chunk(1, 0, 1, b"{}")));
+        assert!(!is_chunk(MESSAGE.as_bytes()));
+    }
+
+    #[test]
+    fn test_decode_plain() {
+        let mut decoder = GelfDecoder::new();
+        let packet = decoder.decode(MESSAGE.as_bytes(), Instant::now()).unwrap();
+        assert_eq!(packet.message, MESSAGE);
+        assert_eq!(packet.msg, "disk full");
+        assert_eq!(packet.hostname.as_deref(), Some("app01"));
+        assert_eq!(packet.format, MessageFormat::Gelf);
+        assert_eq!(packet.severity, Some(3));
+        assert_eq!(packet.structured.unwrap()["_app"], "db");
+    }
+
+    #[test]
+    fn test_decode_compressed() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(MESSAGE.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(MESSAGE.as_bytes()).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        let mut decoder = GelfDecoder::new();
+        for data in [gzip, zlib] {
+            assert!(is_gelf(&data));
+            let packet = decoder.decode(&data, Instant::now()).unwrap();
+            assert_eq!(packet.message, MESSAGE);
+            assert_eq!(packet.msg, "disk full");
+        }
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let mut decoder = GelfDecoder::new();
+        let now = Instant::now();
+        let (first, second) = MESSAGE.as_bytes().split_at(20);
+
+        // Out of order, with a duplicate
+        assert!(decoder.decode(&chunk(7, 1, 2, second), now).is_none());
+        assert!(decoder.decode(&chunk(7, 1, 2, second), now).is_none());
+        let packet = decoder.decode(&chunk(7, 0, 2, first), now).unwrap();
+        assert_eq!(packet.hostname.as_deref(), Some("app01"));
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_decode_chunked_timeout() {
+        let mut decoder = GelfDecoder::new();
+        let now = Instant::now();
+        let (first, second) = MESSAGE.as_bytes().split_at(20);
+
+        assert!(decoder.decode(&chunk(7, 0, 2, first), now).is_none());
+        let later = now + CHUNK_TIMEOUT;
+        assert!(decoder.decode(&chunk(7, 1, 2, second), later).is_none());
+        assert_eq!(decoder.pending.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_chunked_byte_limit() {
+        let mut decoder = GelfDecoder::new();
+        decoder.max_pending_bytes = MESSAGE.len();
+        let now = Instant::now();
+        let (first, second) = MESSAGE.as_bytes().split_at(20);
+
+        assert!(decoder.decode(&chunk(1, 0, 2, first), now).is_none());
+        assert!(decoder.decode(&chunk(2, 1, 2, second), now).is_none());
+        assert_eq!(decoder.pending_bytes, MESSAGE.len());
+        // The buffer is full: neither new messages nor the rest of buffered ones fit
+        assert!(decoder.decode(&chunk(3, 0, 2, first), now).is_none());
+        assert!(decoder.decode(&chunk(1, 1, 2, second), now).is_none());
+        assert_eq!(decoder.pending.len(), 2);
+
+        // Expiry frees the space
+        let later = now + CHUNK_TIMEOUT;
+        assert!(decoder.decode(&chunk(1, 0, 2, first), later).is_none());
+        let packet = decoder.decode(&chunk(1, 1, 2, second), later).unwrap();
+        assert_eq!(packet.msg, "disk full");
+        assert_eq!(decoder.pending_bytes, 0);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let mut decoder = GelfDecoder::new();
+        let now = Instant::now();
+        assert!(decoder.decode(b"{\"host\":\"x\"}", now).is_none());
+        assert!(decoder.decode(b"{not json", now).is_none());
+        assert!(decoder.decode(&chunk(1, 3, 2, b"x"), now).is_none());
+        assert!(decoder.decode(&chunk(1, 0, 200, b"x"), now).is_none());
+        assert!(decoder.decode(&[0x78, 0x9c, 0, 0], now).is_none());
+    }
+}
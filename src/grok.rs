@@ -0,0 +1,246 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+/// The standard patterns shipped with the sniffer
+const BUNDLED_PATTERNS: &str = include_str!("grok-patterns");
+/// Deeper nesting than the bundled library ever needs, catches cycles
+const MAX_DEPTH: usize = 32;
+
+/// Named grok patterns that `%{NAME}` references resolve against.
+#[derive(Debug, Clone)]
+pub struct GrokLibrary {
+    patterns: HashMap<String, String>,
+}
+
+impl Default for GrokLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GrokLibrary {
+    /// A library with the bundled standard patterns, e.g. `SYSLOGBASE` or `IP`.
+    pub fn new() -> Self {
+        let mut library = Self {
+            patterns: HashMap::new(),
+        };
+        library.add_patterns(BUNDLED_PATTERNS);
+        library
+    }
+
+    /// Adds `NAME PATTERN` lines, replacing patterns of the same name.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn add_patterns(&mut self, definitions: &str) {
+        for line in definitions.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((name, pattern)) = line.split_once(char::is_whitespace) {
+                self.patterns
+                    .insert(name.to_string(), pattern.trim_start().to_string());
+            }
+        }
+    }
+
+    /// Adds the patterns from a Logstash-style pattern file.
+    pub fn load_patterns(&mut self, path: &Path) -> Result<(), String> {
+        let definitions = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read grok patterns {}: {}", path.display(), e))?;
+        self.add_patterns(&definitions);
+        Ok(())
+    }
+
+    /// Compiles a grok expression such as `%{SYSLOGBASE} %{IP:client}` into a regex.
+    ///
+    /// `%{NAME:field}` becomes a named capture group. Named references inside library
+    /// patterns, such as the timestamp in `SYSLOGBASE`, don't capture, so only the
+    /// fields the expression names are extracted. A field that is captured more than
+    /// once keeps its first occurrence. The `:type` suffix is accepted and ignored.
+    pub fn compile(&self, grok: &str) -> Result<Regex, String> {
+        let expanded = self.expand(grok, &mut HashSet::new(), 0)?;
+        Regex::new(&expanded).map_err(|e| format!("Invalid grok pattern {}: {}", grok, e))
+    }
+
+    fn expand(
+        &self,
+        pattern: &str,
+        fields: &mut HashSet<String>,
+        depth: usize,
+    ) -> Result<String, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("Grok pattern nested too deeply: {}", pattern));
+        }
+
+        let mut out = String::new();
+        let mut rest = pattern;
+        while let Some(start) = rest.find("%{") {
+            out.push_str(&rest[..start]);
+            let reference = &rest[start + 2..];
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("Unterminated %{{ in grok pattern: {}", pattern))?;
+            let mut parts = reference[..end].splitn(3, ':');
+            let name = parts.next().unwrap_or_default();
+            let field = parts.next();
+
+            let definition = self
+                .patterns
+                .get(name)
+                .ok_or_else(|| format!("Unknown grok pattern: {}", name))?;
+            let inner = self.expand(definition, fields, depth + 1)?;
+            // Writing to a String can't fail
+            let _ = match field {
+                Some(field) if depth == 0 && fields.insert(field.to_string()) => {
+                    write!(out, "(?P<{}>{})", field, inner)
+                }
+                _ => write!(out, "(?:{})", inner),
+            };
+            rest = &reference[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(library: &GrokLibrary, grok: &str, input: &str) -> Vec<(String, String)> {
+        let regex = library.compile(grok).unwrap();
+        let captures = regex.captures(input).unwrap();
+        regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| Some((name.to_string(), captures.name(name)?.as_str().to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_bundled_patterns_compile() {
+        let library = GrokLibrary::new();
+        for name in library.patterns.keys() {
+            assert!(library.compile(&format!("%{{{name}}}")).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_syslogbase() {
+        let library = GrokLibrary::new();
+        let input = "Oct  1 22:14:15 web01.example.com sshd[4321]: Accepted password";
+        // The timestamp, host and pid SYSLOGBASE matches aren't captured
+        let fields = captures(&library, "%{SYSLOGBASE} %{GREEDYDATA:message}", input);
+        assert_eq!(
+            fields,
+            vec![("message".to_string(), "Accepted password".to_string())]
+        );
+
+        let fields = captures(
+            &library,
+            "%{SYSLOGTIMESTAMP} %{SYSLOGHOST:logsource} %{SYSLOGPROG}: %{WORD:outcome}",
+            input,
+        );
+        assert_eq!(
+            fields,
+            vec![
+                ("logsource".to_string(), "web01.example.com".to_string()),
+                ("outcome".to_string(), "Accepted".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_names_and_types() {
+        let library = GrokLibrary::new();
+        let fields = captures(
+            &library,
+            "from %{IP:client} port %{INT:port:int} to %{IP:client}",
+            "from 10.0.0.1 port 22 to 2001:db8::1",
+        );
+        assert_eq!(
+            fields,
+            vec![
+                ("client".to_string(), "10.0.0.1".to_string()),
+                ("port".to_string(), "22".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ip_patterns() {
+        let library = GrokLibrary::new();
+        // The timestamp's colons don't read as an IPv6 address
+        let fields = captures(
+            &library,
+            "%{IP:client}",
+            "Oct 11 22:14:15 sshd: Failed password from 10.0.0.1",
+        );
+        assert_eq!(fields[0].1, "10.0.0.1");
+
+        let ipv6 = library.compile("^%{IPV6}$").unwrap();
+        for address in [
+            "2001:db8::1",
+            "1:2:3:4:5:6:7:8",
+            "::1",
+            "fe80::1%eth0",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(ipv6.is_match(address), "{address}");
+        }
+        for text in ["22:14:15", "1:2", ":::", "2001:db8:::1"] {
+            assert!(!ipv6.is_match(text), "{text}");
+        }
+        let fields = captures(&library, "%{IP:client}", "from ::ffff:10.0.0.1 port");
+        assert_eq!(fields[0].1, "::ffff:10.0.0.1");
+    }
+
+    #[test]
+    fn test_apache_and_rfc5424_patterns() {
+        let library = GrokLibrary::new();
+        let regex = library.compile("^%{COMBINEDAPACHELOG}$").unwrap();
+        assert!(regex.is_match(
+            r#"203.0.113.5 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "http://example.com/" "Mozilla/5.0""#
+        ));
+        assert!(!regex.is_match(r#"203.0.113.5 - - [10/Oct/2000:13:55:36 -0700] "GET /""#));
+
+        let fields = captures(
+            &library,
+            "%{SYSLOG5424BASE} %{GREEDYDATA:message}",
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine su - ID47 [a@1 x="y"] hi"#,
+        );
+        assert_eq!(fields, vec![("message".to_string(), "hi".to_string())]);
+        assert!(library
+            .compile("^%{SYSLOG5424LINE}$")
+            .unwrap()
+            .is_match("<13>1 - - - - - - hello"));
+    }
+
+    #[test]
+    fn test_user_patterns() {
+        let mut library = GrokLibrary::new();
+        library.add_patterns("# comment\n\nTICKET [A-Z]+-%{INT}\nWORD [a-z]+\n");
+        let fields = captures(&library, "%{TICKET:ticket} %{WORD:word}", "OPS-42 done");
+        assert_eq!(fields[0].1, "OPS-42");
+        assert_eq!(fields[1].1, "done");
+
+        assert!(library
+            .load_patterns(Path::new("/nonexistent/patterns"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let mut library = GrokLibrary::new();
+        library.add_patterns("LOOP %{LOOP}");
+        for (grok, error) in [
+            ("%{NOPE}", "Unknown grok pattern: NOPE"),
+            ("%{INT", "Unterminated"),
+            ("%{LOOP}", "nested too deeply"),
+            ("(%{INT}", "Invalid grok pattern"),
+        ] {
+            assert!(library.compile(grok).unwrap_err().contains(error), "{grok}");
+        }
+    }
+}
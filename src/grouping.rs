@@ -0,0 +1,308 @@
+use crate::pri::{facility_name, severity_name};
+use crate::stats::OTHER_VALUES;
+use crate::structured::pointer_value;
+use crate::SyslogPacket;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+/// Value used when a message has nothing for a key, as RFC 5424's NILVALUE
+const MISSING: &str = "-";
+
+/// Where and when a message was captured, beyond what it says about itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Origin {
+    pub source_ip: Option<IpAddr>,
+    /// The address the message was sent to
+    pub collector: Option<IpAddr>,
+    pub vlan: Option<u16>,
+    pub time: Option<SystemTime>,
+}
+
+/// A dimension messages can be grouped by.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupKey {
+    /// The hostname after normalisation
+    Host,
+    SourceIp,
+    App,
+    Facility,
+    Severity,
+    Vlan,
+    Collector,
+    /// A field extracted by a rule, or a JSON pointer into the payload if it starts with `/`
+    Field(String),
+}
+
+impl GroupKey {
+    /// Parses `host`, `source-ip`, `app`, `facility`, `severity`, `vlan`, `collector`
+    /// or `field:NAME`.
+    pub fn parse(key: &str) -> Result<Self, String> {
+        Ok(match key {
+            "host" => Self::Host,
+            "source-ip" => Self::SourceIp,
+            "app" => Self::App,
+            "facility" => Self::Facility,
+            "severity" => Self::Severity,
+            "vlan" => Self::Vlan,
+            "collector" => Self::Collector,
+            _ => match key.strip_prefix("field:") {
+                Some(name) if !name.is_empty() => Self::Field(name.to_string()),
+                _ => return Err(format!("Unknown grouping key: {}", key)),
+            },
+        })
+    }
+
+    /// The key's name in flat output
+    fn name(&self) -> &str {
+        match self {
+            Self::Host => "host",
+            Self::SourceIp => "source_ip",
+            Self::App => "app",
+            Self::Facility => "facility",
+            Self::Severity => "severity",
+            Self::Vlan => "vlan",
+            Self::Collector => "collector",
+            Self::Field(name) => name,
+        }
+    }
+
+    fn value(&self, hostname: &str, packet: &SyslogPacket<'_>, origin: &Origin) -> Option<String> {
+        match self {
+            Self::Host => Some(hostname.to_string()),
+            Self::SourceIp => origin.source_ip.map(|ip| ip.to_string()),
+            Self::App => packet.app_name.as_deref().map(str::to_string),
+            Self::Facility => packet.facility.and_then(facility_name).map(str::to_string),
+            Self::Severity => packet.severity.and_then(severity_name).map(str::to_string),
+            Self::Vlan => origin.vlan.map(|vlan| vlan.to_string()),
+            Self::Collector => origin.collector.map(|ip| ip.to_string()),
+            Self::Field(name) if name.starts_with('/') => packet
+                .structured
+                .as_ref()
+                .and_then(|structured| pointer_value(structured, name)),
+            Self::Field(name) => packet.extracted_field(name).map(str::to_string),
+        }
+    }
+}
+
+/// How grouped counts are laid out in the summary.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq)]
+pub enum GroupOutput {
+    /// One object level per key, with counts at the leaves
+    #[default]
+    Nested,
+    /// A list of objects holding every key's value and the count
+    Flat,
+}
+
+/// Message counts per combination of grouping key values.
+#[derive(Debug, Default)]
+pub struct GroupTracker {
+    keys: Vec<GroupKey>,
+    output: GroupOutput,
+    counts: HashMap<Vec<String>, u64>,
+    max_groups: Option<usize>,
+}
+
+impl GroupTracker {
+    pub fn new(keys: Vec<GroupKey>, output: GroupOutput) -> Self {
+        Self {
+            keys,
+            output,
+            counts: HashMap::new(),
+            max_groups: None,
+        }
+    }
+
+    /// Counts combinations beyond the first `max` together, with every key
+    /// [`OTHER_VALUES`].
+    pub fn set_max_groups(&mut self, max: Option<usize>) {
+        self.max_groups = max;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn add(&mut self, hostname: &str, packet: &SyslogPacket<'_>, origin: &Origin) {
+        if self.keys.is_empty() {
+            return;
+        }
+        let values: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                key.value(hostname, packet, origin)
+                    .unwrap_or_else(|| MISSING.to_string())
+            })
+            .collect();
+        let full = self.max_groups.is_some_and(|max| self.counts.len() >= max);
+        let values = if full && !self.counts.contains_key(&values) {
+            vec![OTHER_VALUES.to_string(); self.keys.len()]
+        } else {
+            values
+        };
+        *self.counts.entry(values).or_insert(0) += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    /// The counts in the configured layout, `None` without grouping keys.
+    pub fn summary(&self) -> Option<Value> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        Some(match self.output {
+            GroupOutput::Nested => {
+                let mut root = Map::new();
+                for (values, count) in &self.counts {
+                    insert_nested(&mut root, values, *count);
+                }
+                Value::Object(root)
+            }
+            GroupOutput::Flat => {
+                let mut rows: Vec<(&Vec<String>, u64)> = self
+                    .counts
+                    .iter()
+                    .map(|(values, count)| (values, *count))
+                    .collect();
+                rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+                Value::Array(rows.into_iter().map(|row| self.flat_row(row)).collect())
+            }
+        })
+    }
+
+    fn flat_row(&self, (values, count): (&Vec<String>, u64)) -> Value {
+        let mut row: Map<String, Value> = self
+            .keys
+            .iter()
+            .zip(values)
+            .map(|(key, value)| (key.name().to_string(), Value::from(value.as_str())))
+            .collect();
+        row.insert("count".to_string(), Value::from(count));
+        Value::Object(row)
+    }
+}
+
+fn insert_nested(node: &mut Map<String, Value>, values: &[String], count: u64) {
+    let Some((value, rest)) = values.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        node.insert(value.clone(), Value::from(count));
+        return;
+    }
+    let child = node
+        .entry(value.clone())
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(child) = child {
+        insert_nested(child, rest, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_syslog_packet;
+    use serde_json::json;
+
+    fn tracker(keys: &[&str], output: GroupOutput) -> GroupTracker {
+        let keys = keys
+            .iter()
+            .map(|key| GroupKey::parse(key).unwrap())
+            .collect();
+        let mut tracker = GroupTracker::new(keys, output);
+        let origin = Origin {
+            source_ip: Some("10.0.0.1".parse().unwrap()),
+            collector: Some("10.0.0.2".parse().unwrap()),
+            vlan: Some(20),
+            time: None,
+        };
+        for message in [
+            "<38>Oct 11 22:14:15 web01 sshd: a",
+            "<38>Oct 11 22:14:15 web01 sshd: b",
+            "<11>Oct 11 22:14:15 web01 nginx: c",
+            "<13>no header",
+        ] {
+            let packet = parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add("web01", &packet, &origin);
+        }
+        tracker
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(GroupKey::parse("source-ip"), Ok(GroupKey::SourceIp));
+        assert_eq!(
+            GroupKey::parse("field:/user"),
+            Ok(GroupKey::Field("/user".to_string()))
+        );
+        assert!(GroupKey::parse("field:").is_err());
+        assert!(GroupKey::parse("hostname").is_err());
+    }
+
+    #[test]
+    fn test_nested_output() {
+        let tracker = tracker(&["host", "app", "severity"], GroupOutput::Nested);
+        assert_eq!(
+            tracker.summary(),
+            Some(json!({
+                "web01": {
+                    "sshd": {"info": 2},
+                    "nginx": {"err": 1},
+                    "-": {"notice": 1}
+                }
+            }))
+        );
+    }
+
+    #[test]
+    fn test_flat_output() {
+        let tracker = tracker(&["facility", "vlan", "collector"], GroupOutput::Flat);
+        assert_eq!(
+            tracker.summary(),
+            Some(json!([
+                {"facility": "auth", "vlan": "20", "collector": "10.0.0.2", "count": 2},
+                {"facility": "user", "vlan": "20", "collector": "10.0.0.2", "count": 2}
+            ]))
+        );
+
+        let mut tracker = GroupTracker::default();
+        assert!(tracker.is_empty());
+        tracker.add(
+            "x",
+            &parse_syslog_packet(b"<13>x").unwrap(),
+            &Origin::default(),
+        );
+        assert_eq!(tracker.summary(), None);
+    }
+
+    #[test]
+    fn test_max_groups() {
+        let mut tracker = GroupTracker::new(vec![GroupKey::Host, GroupKey::App], GroupOutput::Flat);
+        tracker.set_max_groups(Some(2));
+        for message in [
+            "<13>Oct 11 22:14:15 web01 sshd: a",
+            "<13>Oct 11 22:14:15 web02 sshd: a",
+            "<13>Oct 11 22:14:15 web03 sshd: a",
+            "<13>Oct 11 22:14:15 web04 sshd: a",
+            "<13>Oct 11 22:14:15 web01 sshd: a",
+        ] {
+            let packet = parse_syslog_packet(message.as_bytes()).unwrap();
+            let hostname = packet.hostname.as_deref().unwrap();
+            tracker.add(hostname, &packet, &Origin::default());
+        }
+        assert_eq!(
+            tracker.summary(),
+            Some(json!([
+                {"host": "(other)", "app": "(other)", "count": 2},
+                {"host": "web01", "app": "sshd", "count": 2},
+                {"host": "web02", "app": "sshd", "count": 1}
+            ]))
+        );
+    }
+}
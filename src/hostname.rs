@@ -0,0 +1,197 @@
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// One step of the hostname normalisation pipeline.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostStep {
+    /// `WEB01` becomes `web01`
+    Lowercase,
+    /// `web01.corp.example` becomes `web01`, IP addresses are left alone
+    StripDomain,
+    /// Treat the NILVALUE `-` and empty hostnames as missing
+    Nilvalue,
+    /// Use the packet's source IP when there is no hostname
+    SourceIp,
+}
+
+/// Address and alias to name mapping, read from a hosts-style file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostsFile {
+    names: HashMap<String, String>,
+}
+
+impl HostsFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map(|contents| Self::parse(&contents))
+            .map_err(|e| format!("Failed to read hosts file {}: {}", path, e))
+    }
+
+    /// Parses `ADDRESS NAME [ALIAS...]` lines. Both the address and the aliases
+    /// resolve to NAME. `#` starts a comment.
+    pub fn parse(contents: &str) -> Self {
+        let mut names = HashMap::new();
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let (Some(address), Some(name)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            for key in std::iter::once(address).chain(fields) {
+                names.insert(key_for(key).into_owned(), name.to_string());
+            }
+        }
+        Self { names }
+    }
+
+    /// Looks up an IP address or alias, ignoring case.
+    pub fn resolve(&self, host: &str) -> Option<&str> {
+        self.names.get(key_for(host).as_ref()).map(String::as_str)
+    }
+}
+
+/// Addresses in canonical form, so `::0001` matches `::1`, and names lowercased.
+fn key_for(host: &str) -> Cow<'_, str> {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => Cow::Owned(ip.to_string()),
+        Err(_) => lowercase(Cow::Borrowed(host)),
+    }
+}
+
+fn lowercase(host: Cow<'_, str>) -> Cow<'_, str> {
+    if host.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(host.to_ascii_lowercase())
+    } else {
+        host
+    }
+}
+
+fn strip_domain(host: Cow<'_, str>) -> Cow<'_, str> {
+    let Some(dot) = host.find('.') else {
+        return host;
+    };
+    if dot == 0 || host.parse::<IpAddr>().is_ok() {
+        return host;
+    }
+    match host {
+        Cow::Borrowed(host) => Cow::Borrowed(&host[..dot]),
+        Cow::Owned(mut host) => {
+            host.truncate(dot);
+            Cow::Owned(host)
+        }
+    }
+}
+
+/// Turns the header hostname into the key hosts are tracked under.
+#[derive(Debug, Clone, Default)]
+pub struct HostNormalizer {
+    steps: Vec<HostStep>,
+    hosts: Option<HostsFile>,
+}
+
+impl HostNormalizer {
+    /// The steps run in the given order, the hosts file lookup runs last.
+    pub fn new(steps: Vec<HostStep>, hosts: Option<HostsFile>) -> Self {
+        Self { steps, hosts }
+    }
+
+    /// Only allocates when a step has to change the hostname.
+    pub fn normalize<'a>(
+        &'a self,
+        hostname: Option<&'a str>,
+        source_ip: Option<IpAddr>,
+    ) -> Option<Cow<'a, str>> {
+        let mut host = hostname.map(Cow::Borrowed);
+        for step in &self.steps {
+            host = match (step, host) {
+                (HostStep::Lowercase, Some(host)) => Some(lowercase(host)),
+                (HostStep::StripDomain, Some(host)) => Some(strip_domain(host)),
+                (HostStep::Nilvalue, Some(host)) if host.is_empty() || host == "-" => None,
+                (HostStep::SourceIp, None) => source_ip.map(|ip| Cow::Owned(ip.to_string())),
+                (_, host) => host,
+            };
+        }
+
+        match (&self.hosts, host) {
+            (Some(hosts), Some(host)) => match hosts.resolve(&host) {
+                Some(name) => Some(Cow::Borrowed(name)),
+                None => Some(host),
+            },
+            (_, host) => host,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTS: &str = "\
+# address   name        aliases
+10.0.0.1    web01       WWW www.corp.example
+::0001      localhost6  # comment
+bogus
+";
+
+    fn normalize(normalizer: &HostNormalizer, host: Option<&str>) -> Option<String> {
+        let source = Some(IpAddr::from([10, 0, 0, 1]));
+        normalizer.normalize(host, source).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn test_hosts_file() {
+        let hosts = HostsFile::parse(HOSTS);
+        assert_eq!(hosts.resolve("10.0.0.1"), Some("web01"));
+        assert_eq!(hosts.resolve("www"), Some("web01"));
+        assert_eq!(hosts.resolve("WWW.corp.example"), Some("web01"));
+        assert_eq!(hosts.resolve("::1"), Some("localhost6"));
+        assert_eq!(hosts.resolve("web01"), None);
+        assert_eq!(hosts.resolve("bogus"), None);
+
+        assert!(HostsFile::load("/nonexistent/hosts").is_err());
+    }
+
+    #[test]
+    fn test_normalize_steps() {
+        let all = vec![
+            HostStep::Lowercase,
+            HostStep::StripDomain,
+            HostStep::Nilvalue,
+            HostStep::SourceIp,
+        ];
+        let normalizer = HostNormalizer::new(all, None);
+        assert_eq!(normalize(&normalizer, Some("WEB01")).unwrap(), "web01");
+        assert_eq!(normalize(&normalizer, Some("web01.Corp")).unwrap(), "web01");
+        assert_eq!(
+            normalize(&normalizer, Some("10.1.2.3")).unwrap(),
+            "10.1.2.3"
+        );
+        assert_eq!(normalize(&normalizer, Some("-")).unwrap(), "10.0.0.1");
+        assert_eq!(normalize(&normalizer, None).unwrap(), "10.0.0.1");
+        assert_eq!(normalizer.normalize(None, None), None);
+
+        // Unchanged hostnames stay borrowed
+        let host = normalizer.normalize(Some("web01"), None);
+        assert!(matches!(host, Some(Cow::Borrowed("web01"))));
+
+        let normalizer = HostNormalizer::default();
+        assert_eq!(normalize(&normalizer, Some("-")).unwrap(), "-");
+        assert_eq!(normalize(&normalizer, None), None);
+    }
+
+    #[test]
+    fn test_normalize_order_and_hosts_file() {
+        // The NILVALUE is only mapped once the source IP fallback has already run
+        let steps = vec![HostStep::SourceIp, HostStep::Nilvalue];
+        let normalizer = HostNormalizer::new(steps, None);
+        assert_eq!(normalize(&normalizer, Some("-")), None);
+
+        let steps = vec![HostStep::Nilvalue, HostStep::SourceIp];
+        let normalizer = HostNormalizer::new(steps, Some(HostsFile::parse(HOSTS)));
+        assert_eq!(normalize(&normalizer, Some("-")).unwrap(), "web01");
+        assert_eq!(normalize(&normalizer, Some("www")).unwrap(), "web01");
+        assert_eq!(normalize(&normalizer, Some("db01")).unwrap(), "db01");
+    }
+}
@@ -1,15 +1,41 @@
 pub mod app;
+pub mod auth;
 pub mod capture;
+pub mod cardinality;
+pub mod compliance;
 pub mod config;
+pub mod decode;
+pub mod escapes;
+pub mod events;
+pub mod framing;
+pub mod gelf;
+pub mod grok;
+pub mod grouping;
+pub mod hostname;
+mod parser;
+pub mod pri;
+pub mod rate;
+pub mod reassembly;
+pub mod relay;
+pub mod rules;
+pub mod sampling;
+pub mod serialize;
+pub mod sizes;
 pub mod stats;
+pub mod structured;
+pub mod templates;
+pub mod windows;
 
-use regex::Regex;
+use compliance::Violation;
 use serde::Serialize;
-use std::sync::OnceLock;
+use std::borrow::Cow;
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PacketData {
     pub data: Vec<u8>,
+    /// When the packet was captured, if the source records it
+    pub timestamp: Option<SystemTime>,
 }
 
 pub trait PacketSource {
@@ -17,56 +43,174 @@ pub trait PacketSource {
     fn get_datalink(&self) -> String;
 }
 
+/// Wire format a message was recognised as.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    Rfc5424,
+    Rfc3164,
+    Gelf,
+    /// No recognised header, e.g. a bare `<PRI>` followed by free text
+    Unknown,
+}
+
+/// A parsed message. Text fields borrow from the captured payload where possible,
+/// use [`SyslogPacket::into_owned`] to keep a packet beyond the payload's lifetime.
 #[derive(Debug, Serialize, Clone, PartialEq)]
-pub struct SyslogPacket {
-    pub message: String,
-    pub hostname: Option<String>,
+pub struct SyslogPacket<'a> {
+    /// The complete message as received, decoded with [`ParseOptions::unescape`]
+    pub message: Cow<'a, str>,
+    /// Size in bytes of the message as received, before any decoding
+    #[serde(skip)]
+    pub size: usize,
+    /// Timestamp as written by the sender, RFC 3339 or `Mmm dd hh:mm:ss`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<Cow<'a, str>>,
+    /// The header's HOSTNAME, or a Windows event's computer name if the header has none
+    pub hostname: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub procid: Option<Cow<'a, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msgid: Option<Cow<'a, str>>,
+    /// RFC 5424 STRUCTURED-DATA, still escaped, see [`serialize::parse_structured_data`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_data: Option<Cow<'a, str>>,
+    /// The MSG part, i.e. the message without its header
+    pub msg: Cow<'a, str>,
+    /// JSON payload carried in the MSG, either `@cee:` prefixed or bare
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured: Option<serde_json::Value>,
+    /// Named fields pulled out of the MSG by [`rules::RuleSet`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extracted: Vec<(String, String)>,
+    /// A netfilter, auditd, authentication or Windows event recognised in the MSG
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<events::Event>,
+    /// How control characters in the MSG are encoded, if it has any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub escapes: Vec<escapes::Escape>,
+    pub format: MessageFormat,
+    pub facility: Option<u8>,
+    pub severity: Option<u8>,
+    /// RFC deviations, only filled in when parsing with [`ParseOptions::strict`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
+
+/// A packet that owns all of its data.
+pub type OwnedSyslogPacket = SyslogPacket<'static>;
+
+fn owned(field: Option<Cow<'_, str>>) -> Option<Cow<'static, str>> {
+    field.map(|field| Cow::Owned(field.into_owned()))
+}
+
+impl SyslogPacket<'_> {
+    pub fn into_owned(self) -> OwnedSyslogPacket {
+        SyslogPacket {
+            message: Cow::Owned(self.message.into_owned()),
+            size: self.size,
+            timestamp: owned(self.timestamp),
+            hostname: owned(self.hostname),
+            app_name: owned(self.app_name),
+            procid: owned(self.procid),
+            msgid: owned(self.msgid),
+            structured_data: owned(self.structured_data),
+            msg: Cow::Owned(self.msg.into_owned()),
+            structured: self.structured,
+            extracted: self.extracted,
+            event: self.event,
+            escapes: self.escapes,
+            format: self.format,
+            facility: self.facility,
+            severity: self.severity,
+            violations: self.violations,
+        }
+    }
+
+    /// The value of a field extracted by a rule, the first one if several matched.
+    pub fn extracted_field(&self, name: &str) -> Option<&str> {
+        self.extracted
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The header's hostname, or the computer a Windows event names if the header has none.
+fn hostname_or_computer<'a>(
+    hostname: Option<&'a str>,
+    event: Option<&events::Event>,
+) -> Option<Cow<'a, str>> {
+    match (hostname, event) {
+        (Some(host), _) if !host.is_empty() && host != "-" => Some(Cow::Borrowed(host)),
+        (_, Some(events::Event::Windows(windows))) if windows.computer.is_some() => {
+            windows.computer.clone().map(Cow::Owned)
+        }
+        (host, _) => host.map(Cow::Borrowed),
+    }
 }
 
-fn rfc5424_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| {
-        // <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCT-DATA MSG
-        // We just want to grab the HOSTNAME (4th field)
-        // Example: <165>1 2003-10-11T22:14:15.003Z mymachine.example.com ...
-        Regex::new(r"^<(\d{1,3})>(\d)\s+(\S+)\s+(\S+)\s+").unwrap()
-    })
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ParseOptions {
+    /// Check the message against RFC 3164/5424 and record every violation
+    pub strict: bool,
+    /// Decode `#012`- and `\n`-style escapes in the message, see [`escapes::unescape`]
+    pub unescape: bool,
 }
 
-fn rfc3164_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| {
-        // <PRI>TIMESTAMP HOSTNAME MSG
-        // Example: <13>Oct 11 22:14:15 mymachine su: ...
-        Regex::new(r"^<(\d{1,3})>([A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2})\s+(\S+)\s+").unwrap()
-    })
+/// Parses a syslog message without copying it.
+///
+/// The only allocations are for messages carrying a JSON payload, which is parsed
+/// into [`SyslogPacket::structured`], a record recognised as an [`events::Event`],
+/// or control characters.
+pub fn parse_syslog_packet(packet: &[u8]) -> Option<SyslogPacket<'_>> {
+    parse_syslog_packet_with(packet, &ParseOptions::default())
 }
 
-pub fn parse_syslog_packet(packet: &[u8]) -> Option<SyslogPacket> {
+pub fn parse_syslog_packet_with<'a>(
+    packet: &'a [u8],
+    options: &ParseOptions,
+) -> Option<SyslogPacket<'a>> {
     if packet.is_empty() {
         return None;
     }
 
     match std::str::from_utf8(packet) {
         Ok(s) => {
-            let mut hostname = None;
-
-            // Try RFC 5424
-            if let Some(caps) = rfc5424_regex().captures(s) {
-                if let Some(host) = caps.get(4) {
-                    hostname = Some(host.as_str().to_string());
-                }
-            }
-            // Try RFC 3164
-            else if let Some(caps) = rfc3164_regex().captures(s) {
-                if let Some(host) = caps.get(3) {
-                    hostname = Some(host.as_str().to_string());
-                }
-            }
-
+            let header = parser::parse_header(s);
+            let escapes = escapes::detect(header.msg);
+            let structured = structured::parse_structured(header.msg);
+            // A JSON payload's escapes are part of the JSON, decoding them would break it
+            let (message, msg) = if options.unescape && structured.is_none() {
+                (escapes::unescape(s), escapes::unescape(header.msg))
+            } else {
+                (Cow::Borrowed(s), Cow::Borrowed(header.msg))
+            };
+            let event = events::parse_event(&msg, structured.as_ref());
             Some(SyslogPacket {
-                message: s.to_string(),
-                hostname,
+                message,
+                size: s.len(),
+                timestamp: header.timestamp.map(Cow::Borrowed),
+                hostname: hostname_or_computer(header.hostname, event.as_ref()),
+                app_name: header.app_name.map(Cow::Borrowed),
+                procid: header.procid.map(Cow::Borrowed),
+                msgid: header.msgid.map(Cow::Borrowed),
+                structured_data: header.structured_data.map(Cow::Borrowed),
+                msg,
+                extracted: Vec::new(),
+                structured,
+                event,
+                escapes,
+                format: header.format,
+                facility: header.pri.map(|(facility, _)| facility),
+                severity: header.pri.map(|(_, severity)| severity),
+                violations: if options.strict {
+                    compliance::check(s)
+                } else {
+                    Vec::new()
+                },
             })
         }
         Err(_) => None,
@@ -103,11 +247,120 @@ mod tests {
         assert!(packet.hostname.is_none());
     }
 
+    #[test]
+    fn test_parse_format_and_pri() {
+        let packet = parse_syslog_packet(b"<13>Oct 11 22:14:15 mymachine su: su root").unwrap();
+        assert_eq!(packet.format, MessageFormat::Rfc3164);
+        assert_eq!(packet.facility, Some(1));
+        assert_eq!(packet.severity, Some(5));
+
+        let packet =
+            parse_syslog_packet(b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com su - - -")
+                .unwrap();
+        assert_eq!(packet.format, MessageFormat::Rfc5424);
+        assert_eq!(packet.facility, Some(20));
+        assert_eq!(packet.severity, Some(5));
+
+        let packet = parse_syslog_packet(b"<13>Hello world").unwrap();
+        assert_eq!(packet.format, MessageFormat::Unknown);
+        assert_eq!(packet.severity, Some(5));
+        assert_eq!(packet.msg, "Hello world");
+
+        let packet = parse_syslog_packet(b"<999>Hello world").unwrap();
+        assert_eq!(packet.facility, None);
+        assert_eq!(packet.severity, None);
+    }
+
+    #[test]
+    fn test_parse_structured_payload() {
+        let data = r#"<13>Oct 11 22:14:15 mymachine app: @cee: {"user":"alice"}"#.as_bytes();
+        let packet = parse_syslog_packet(data).unwrap();
+        assert_eq!(packet.hostname.as_deref(), Some("mymachine"));
+        assert_eq!(packet.structured.unwrap()["user"], "alice");
+
+        let packet = parse_syslog_packet(b"<13>Hello world").unwrap();
+        assert!(packet.structured.is_none());
+
+        let options = ParseOptions {
+            unescape: true,
+            ..ParseOptions::default()
+        };
+        let data = br#"<13>Oct 11 22:14:15 mymachine app: {"msg":"a\nb"}"#;
+        let packet = parse_syslog_packet_with(data, &options).unwrap();
+        assert_eq!(packet.structured.unwrap()["msg"], "a\nb");
+        assert_eq!(packet.msg, r#"{"msg":"a\nb"}"#);
+    }
+
+    #[test]
+    fn test_parse_windows_computer() {
+        let data = b"<14>WIN-DC01\tMSWinEventLog\t1\tSecurity\t7\tdate\t4625";
+        let packet = parse_syslog_packet(data).unwrap();
+        assert_eq!(packet.hostname.as_deref(), Some("WIN-DC01"));
+
+        let data = br#"<14>1 2024-10-11T22:14:15Z - nxlog - - - {"EventID":4624,"Channel":"Security","Hostname":"WIN-WEB1"}"#;
+        let packet = parse_syslog_packet(data).unwrap();
+        assert_eq!(packet.hostname.as_deref(), Some("WIN-WEB1"));
+
+        let data = br#"<14>Oct 11 22:14:15 relay01 nxlog: {"EventID":4624,"Channel":"Security","Hostname":"WIN-WEB1"}"#;
+        let packet = parse_syslog_packet(data).unwrap();
+        assert_eq!(packet.hostname.as_deref(), Some("relay01"));
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let data = b"<999>Oct 01 22:14:15 my_machine su: su root";
+        assert!(parse_syslog_packet(data).unwrap().violations.is_empty());
+
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let packet = parse_syslog_packet_with(data, &options).unwrap();
+        assert_eq!(
+            packet.violations,
+            vec![Violation::PriOutOfRange, Violation::BadTimestamp]
+        );
+
+        let data = b"<13>Oct 11 22:14:15 mymachine su: su root";
+        let packet = parse_syslog_packet_with(data, &options).unwrap();
+        assert!(packet.violations.is_empty());
+    }
+
+    #[test]
+    fn test_parse_borrows_payload() {
+        let data = b"<13>Oct 11 22:14:15 mymachine su: su root".to_vec();
+        let packet = parse_syslog_packet(&data).unwrap();
+        assert!(matches!(packet.message, Cow::Borrowed(_)));
+        assert!(matches!(packet.hostname, Some(Cow::Borrowed("mymachine"))));
+        assert!(matches!(packet.app_name, Some(Cow::Borrowed("su"))));
+        assert!(matches!(packet.msg, Cow::Borrowed("su root")));
+
+        let owned: OwnedSyslogPacket = packet.clone().into_owned();
+        drop(data);
+        assert!(matches!(owned.message, Cow::Owned(_)));
+        assert_eq!(owned.hostname.as_deref(), Some("mymachine"));
+    }
+
     #[test]
     fn test_debug_impls() {
         let packet = SyslogPacket {
-            message: "msg".to_string(),
-            hostname: Some("host".to_string()),
+            message: "msg".into(),
+            size: 3,
+            timestamp: None,
+            hostname: Some("host".into()),
+            app_name: None,
+            procid: None,
+            msgid: None,
+            structured_data: None,
+            msg: "msg".into(),
+            structured: None,
+            extracted: Vec::new(),
+            event: None,
+            escapes: Vec::new(),
+            format: MessageFormat::Unknown,
+            facility: None,
+            severity: None,
+            violations: Vec::new(),
         };
         let debug_str = format!("{:?}", packet);
         assert!(debug_str.contains("SyslogPacket"));
@@ -119,6 +372,7 @@ mod tests {
 
         let data = PacketData {
             data: vec![1, 2, 3],
+            timestamp: None,
         };
         let data_debug = format!("{:?}", data);
         assert!(data_debug.contains("PacketData"));
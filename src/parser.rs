@@ -0,0 +1,433 @@
+use crate::compliance::{MAX_APP_NAME_LEN, MAX_PROCID_LEN, MONTHS};
+use crate::MessageFormat;
+
+/// A message split into its header fields, all borrowed from the input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Header<'a> {
+    pub format: MessageFormat,
+    /// (facility, severity), `None` if there is no valid `<PRI>`
+    pub pri: Option<(u8, u8)>,
+    pub timestamp: Option<&'a str>,
+    pub hostname: Option<&'a str>,
+    pub app_name: Option<&'a str>,
+    pub procid: Option<&'a str>,
+    pub msgid: Option<&'a str>,
+    /// STRUCTURED-DATA as it appears on the wire, still escaped
+    pub structured_data: Option<&'a str>,
+    /// Everything after the header
+    pub msg: &'a str,
+}
+
+/// Byte cursor over a message. All methods leave the position unchanged on failure.
+struct Scanner<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.peek() == Some(byte);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    /// Reads between `min` and `max` ASCII digits as a number.
+    fn number(&mut self, min: usize, max: usize) -> Option<u32> {
+        let digits = self.input.as_bytes()[self.pos..]
+            .iter()
+            .take(max)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits < min {
+            return None;
+        }
+        let value = self.input.as_bytes()[self.pos..self.pos + digits]
+            .iter()
+            .fold(0, |acc, b| acc * 10 + u32::from(b - b'0'));
+        self.pos += digits;
+        Some(value)
+    }
+
+    /// Skips one or more spaces or tabs.
+    fn spaces(&mut self) -> bool {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    /// Reads a non-empty run of non-whitespace bytes.
+    fn token(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        // Whitespace is ASCII, so both ends are on char boundaries
+        (self.pos > start).then(|| &self.input[start..self.pos])
+    }
+
+    /// Reads a possibly empty run of bytes matching `f`.
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        // Callers stop at ASCII bytes, so both ends are on char boundaries
+        &self.input[start..self.pos]
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn attempt<T>(&mut self, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let start = self.pos;
+        let result = f(self);
+        if result.is_none() {
+            self.pos = start;
+        }
+        result
+    }
+}
+
+/// `<PRI>` with PRI in 0..=191.
+fn pri(scanner: &mut Scanner) -> Option<(u8, u8)> {
+    scanner.attempt(|s| {
+        if !s.eat(b'<') {
+            return None;
+        }
+        let pri = s.number(1, 3).filter(|pri| *pri <= 191)?;
+        s.eat(b'>').then_some(((pri / 8) as u8, (pri % 8) as u8))
+    })
+}
+
+/// Maps the RFC 5424 NILVALUE to `None`.
+fn nil(field: &str) -> Option<&str> {
+    (field != "-").then_some(field)
+}
+
+/// `VERSION SP TIMESTAMP SP HOSTNAME SP`, returning the timestamp and hostname
+fn rfc5424_prefix<'a>(scanner: &mut Scanner<'a>) -> Option<(&'a str, &'a str)> {
+    scanner.attempt(|s| {
+        s.number(1, 3)?;
+        if !s.spaces() {
+            return None;
+        }
+        let timestamp = s.token()?;
+        if !s.spaces() {
+            return None;
+        }
+        let hostname = s.token()?;
+        s.spaces().then_some((timestamp, hostname))
+    })
+}
+
+/// `APP-NAME SP PROCID SP MSGID SP STRUCTURED-DATA`
+fn rfc5424_fields<'a>(scanner: &mut Scanner<'a>) -> Option<([&'a str; 3], &'a str)> {
+    scanner.attempt(|s| {
+        let mut ids = [""; 3];
+        for id in &mut ids {
+            *id = s.token()?;
+            if !s.spaces() {
+                return None;
+            }
+        }
+        Some((ids, structured_data(s)?))
+    })
+}
+
+/// `-` or one or more `[...]` elements. Quoted values may contain escaped `"` and `]`.
+fn structured_data<'a>(scanner: &mut Scanner<'a>) -> Option<&'a str> {
+    scanner.attempt(|s| {
+        let start = s.pos;
+        if s.eat(b'-') {
+            return Some("-");
+        }
+        while s.eat(b'[') {
+            let mut quoted = false;
+            loop {
+                match (s.peek()?, quoted) {
+                    (b'\\', true) => s.pos += 1,
+                    (b'"', _) => quoted = !quoted,
+                    (b']', false) => break,
+                    _ => {}
+                }
+                s.pos += 1;
+            }
+            s.pos += 1;
+        }
+        (s.pos > start).then(|| &s.input[start..s.pos])
+    })
+}
+
+/// `Mmm dd hh:mm:ss SP HOSTNAME SP`, returning the timestamp and hostname
+fn rfc3164_prefix<'a>(scanner: &mut Scanner<'a>) -> Option<(&'a str, &'a str)> {
+    scanner.attempt(|s| {
+        let start = s.pos;
+        let month = s.input.as_bytes().get(s.pos..s.pos + 3)?;
+        if !MONTHS.iter().any(|m| m.as_bytes() == month) {
+            return None;
+        }
+        s.pos += 3;
+        if !s.spaces() {
+            return None;
+        }
+        s.number(1, 2)?;
+        if !s.spaces() {
+            return None;
+        }
+        s.number(2, 2)?;
+        for _ in 0..2 {
+            if !s.eat(b':') {
+                return None;
+            }
+            s.number(2, 2)?;
+        }
+        let timestamp = &s.input[start..s.pos];
+        if !s.spaces() {
+            return None;
+        }
+        let hostname = s.token()?;
+        // Exactly one separator, the MSG keeps any further whitespace
+        (s.eat(b' ') || s.eat(b'\t')).then_some((timestamp, hostname))
+    })
+}
+
+/// `TAG[PID]: ` after an RFC 3164 header. Also accepts the usual malformed
+/// variants: no colon after a PID, spaces around the PID or before the colon,
+/// and a missing closing bracket. A bare word without colon or PID isn't a TAG.
+fn rfc3164_tag<'a>(scanner: &mut Scanner<'a>) -> Option<(&'a str, Option<&'a str>)> {
+    scanner.attempt(|s| {
+        let tag = s.take_while(|b| !b.is_ascii_whitespace() && !matches!(b, b'[' | b':'));
+        // A JSON payload's first key isn't a tag
+        if tag.is_empty() || tag.len() > MAX_APP_NAME_LEN || tag.starts_with('{') {
+            return None;
+        }
+
+        let pid = s.attempt(|s| {
+            s.spaces();
+            if !s.eat(b'[') {
+                return None;
+            }
+            s.spaces();
+            let pid = s.take_while(|b| !b.is_ascii_whitespace() && !matches!(b, b']' | b':'));
+            s.spaces();
+            s.eat(b']');
+            Some(pid)
+        });
+        let colon = s
+            .attempt(|s| {
+                s.spaces();
+                s.eat(b':').then_some(())
+            })
+            .is_some();
+        if !colon && pid.is_none() {
+            return None;
+        }
+
+        s.eat(b' ');
+        let pid = pid.filter(|pid| !pid.is_empty() && pid.len() <= MAX_PROCID_LEN);
+        Some((tag, pid))
+    })
+}
+
+/// Whether an RFC 3164 parser would read the start of `msg` as a TAG.
+pub(crate) fn starts_with_tag(msg: &str) -> bool {
+    rfc3164_tag(&mut Scanner::new(msg)).is_some()
+}
+
+/// Scans the header in a single pass. The byte after the PRI decides the format:
+/// a version digit for RFC 5424, a month name for RFC 3164.
+pub(crate) fn parse_header(input: &str) -> Header<'_> {
+    let mut scanner = Scanner::new(input);
+    let mut header = Header {
+        format: MessageFormat::Unknown,
+        pri: pri(&mut scanner),
+        timestamp: None,
+        hostname: None,
+        app_name: None,
+        procid: None,
+        msgid: None,
+        structured_data: None,
+        msg: "",
+    };
+
+    match (header.pri, scanner.peek()) {
+        (Some(_), Some(b'1'..=b'9')) => {
+            if let Some((timestamp, hostname)) = rfc5424_prefix(&mut scanner) {
+                header.format = MessageFormat::Rfc5424;
+                header.timestamp = nil(timestamp);
+                header.hostname = Some(hostname);
+                if let Some(([app_name, procid, msgid], sd)) = rfc5424_fields(&mut scanner) {
+                    header.app_name = nil(app_name);
+                    header.procid = nil(procid);
+                    header.msgid = nil(msgid);
+                    header.structured_data = nil(sd);
+                    scanner.eat(b' ');
+                }
+            }
+        }
+        (Some(_), Some(b'A'..=b'Z')) => {
+            if let Some((timestamp, hostname)) = rfc3164_prefix(&mut scanner) {
+                header.format = MessageFormat::Rfc3164;
+                header.timestamp = Some(timestamp);
+                header.hostname = Some(hostname);
+                if let Some((tag, pid)) = rfc3164_tag(&mut scanner) {
+                    header.app_name = Some(tag);
+                    header.procid = pid;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    header.msg = scanner.rest();
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let header =
+            parse_header("<165>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - msg");
+        assert_eq!(header.format, MessageFormat::Rfc5424);
+        assert_eq!(header.pri, Some((20, 5)));
+        assert_eq!(header.hostname, Some("mymachine.example.com"));
+        assert_eq!(header.timestamp, Some("2003-10-11T22:14:15.003Z"));
+        assert_eq!(header.app_name, Some("su"));
+        assert_eq!(header.procid, None);
+        assert_eq!(header.msgid, Some("ID47"));
+        assert_eq!(header.structured_data, None);
+        assert_eq!(header.msg, "msg");
+    }
+
+    #[test]
+    fn test_parse_rfc5424_structured_data() {
+        let input = r#"<165>1 - host app 42 - [a@1 x="q\"]"][b y="z"]  two spaces"#;
+        let header = parse_header(input);
+        assert_eq!(header.structured_data, Some(r#"[a@1 x="q\"]"][b y="z"]"#));
+        assert_eq!(header.procid, Some("42"));
+        assert_eq!(header.msg, " two spaces");
+
+        // Unterminated SD: the header up to the hostname is still usable
+        let header = parse_header(r#"<165>1 - host app - - [a x="y" msg"#);
+        assert_eq!(header.hostname, Some("host"));
+        assert_eq!(header.app_name, None);
+        assert_eq!(header.msg, r#"app - - [a x="y" msg"#);
+
+        let header = parse_header("<165>1 - host app - - -");
+        assert_eq!(header.msg, "");
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        let header = parse_header("<13>Oct  1 22:14:15 mymachine su: su root");
+        assert_eq!(header.format, MessageFormat::Rfc3164);
+        assert_eq!(header.pri, Some((1, 5)));
+        assert_eq!(header.hostname, Some("mymachine"));
+        assert_eq!(header.timestamp, Some("Oct  1 22:14:15"));
+        assert_eq!(header.app_name, Some("su"));
+        assert_eq!(header.procid, None);
+        assert_eq!(header.msg, "su root");
+    }
+
+    #[test]
+    fn test_parse_rfc3164_tag() {
+        for (content, tag, pid, msg) in [
+            (
+                "sshd[1234]: Accepted",
+                Some("sshd"),
+                Some("1234"),
+                "Accepted",
+            ),
+            (
+                "postfix/smtpd[99]:  two",
+                Some("postfix/smtpd"),
+                Some("99"),
+                " two",
+            ),
+            ("CRON[42] no colon", Some("CRON"), Some("42"), "no colon"),
+            (
+                "sshd [ 1234 ] : spaced",
+                Some("sshd"),
+                Some("1234"),
+                "spaced",
+            ),
+            (
+                "sshd[1234: unclosed",
+                Some("sshd"),
+                Some("1234"),
+                "unclosed",
+            ),
+            ("kernel:no space", Some("kernel"), None, "no space"),
+            ("app[]: empty pid", Some("app"), None, "empty pid"),
+            ("Hello world", None, None, "Hello world"),
+            ("[1234]: no tag", None, None, "[1234]: no tag"),
+            (": empty", None, None, ": empty"),
+            (r#"{"a":1}"#, None, None, r#"{"a":1}"#),
+            ("", None, None, ""),
+        ] {
+            let input = format!("<13>Oct 11 22:14:15 host {content}");
+            let header = parse_header(&input);
+            assert_eq!(header.app_name, tag, "{content}");
+            assert_eq!(header.procid, pid, "{content}");
+            assert_eq!(header.msg, msg, "{content}");
+        }
+    }
+
+    #[test]
+    fn test_parse_pri_range() {
+        assert_eq!(parse_header("<0>x").pri, Some((0, 0)));
+        assert_eq!(parse_header("<191>x").pri, Some((23, 7)));
+        assert_eq!(parse_header("<192>x").pri, None);
+        assert_eq!(parse_header("<1000>x").pri, None);
+        assert_eq!(parse_header("<>x").pri, None);
+        assert_eq!(parse_header("<13x").pri, None);
+
+        let header = parse_header("<999>1 2003-10-11T22:14:15.003Z mymachine su - - -");
+        assert_eq!(header.format, MessageFormat::Unknown);
+        assert_eq!(header.hostname, None);
+    }
+
+    #[test]
+    fn test_parse_incomplete_headers() {
+        for input in [
+            "",
+            "<13>",
+            "<13>Hello world",
+            "<13>1 2003-10-11T22:14:15.003Z",
+            "<13>1 2003-10-11T22:14:15.003Z host",
+            "<13>0 2003-10-11T22:14:15.003Z host su",
+            "<13>Foo 11 22:14:15 mymachine su",
+            "<13>Oct 11 22:14 mymachine su",
+            "<13>Oct 11 22:14:15 mymachine",
+        ] {
+            let header = parse_header(input);
+            assert_eq!(header.hostname, None, "{input}");
+            assert_eq!(header.timestamp, None, "{input}");
+            assert_eq!(header.format, MessageFormat::Unknown, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_long_garbage() {
+        let garbage = format!("<13>1 {}", " ".repeat(1_000_000));
+        assert_eq!(parse_header(&garbage).hostname, None);
+        let garbage = "<".repeat(1_000_000);
+        assert_eq!(parse_header(&garbage).pri, None);
+    }
+}
@@ -0,0 +1,55 @@
+/// Severity keywords from RFC 5424 table 2, indexed by severity code.
+pub const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Facility keywords as used by syslog.conf, indexed by facility code.
+pub const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+pub fn severity_name(severity: u8) -> Option<&'static str> {
+    SEVERITIES.get(usize::from(severity)).copied()
+}
+
+pub fn facility_name(facility: u8) -> Option<&'static str> {
+    FACILITIES.get(usize::from(facility)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(severity_name(0), Some("emerg"));
+        assert_eq!(severity_name(7), Some("debug"));
+        assert_eq!(severity_name(8), None);
+        assert_eq!(facility_name(4), Some("auth"));
+        assert_eq!(facility_name(23), Some("local7"));
+        assert_eq!(facility_name(24), None);
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The busiest second seen, from capture timestamps.
+#[derive(Debug, Clone, Default)]
+pub struct PeakRate {
+    /// Unix time of the second being counted and the messages in it so far
+    second: u64,
+    count: u64,
+    peak_second: u64,
+    peak: u64,
+}
+
+impl PeakRate {
+    /// Counts a message captured at `time`. Messages arriving out of order, e.g.
+    /// after reassembly, count towards the current second.
+    pub fn add(&mut self, time: SystemTime) {
+        let second = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        if second > self.second {
+            self.second = second;
+            self.count = 0;
+        }
+        self.count += 1;
+        if self.count > self.peak {
+            self.peak = self.count;
+            self.peak_second = self.second;
+        }
+    }
+
+    /// Messages in the busiest second and its Unix time, `None` before any message.
+    pub fn peak(&self) -> Option<(u64, u64)> {
+        (self.peak > 0).then_some((self.peak, self.peak_second))
+    }
+}
+
+/// Average messages per second over an interval, rounded to two decimals.
+pub fn per_second(count: u64, interval_seconds: u64) -> f64 {
+    let rate = count as f64 / interval_seconds.max(1) as f64;
+    (rate * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_peak() {
+        let mut rate = PeakRate::default();
+        assert_eq!(rate.peak(), None);
+        for millis in [1000, 1500, 2000, 2100, 2200, 2999, 1200, 3000] {
+            rate.add(at(millis));
+        }
+        assert_eq!(rate.peak(), Some((5, 2)));
+    }
+
+    #[test]
+    fn test_per_second() {
+        assert_eq!(per_second(10, 3), 3.33);
+        assert_eq!(per_second(7, 0), 7.0);
+        assert_eq!(per_second(0, 10), 0.0);
+    }
+}
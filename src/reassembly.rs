@@ -0,0 +1,222 @@
+use crate::{OwnedSyslogPacket, SyslogPacket};
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Continuation patterns used unless configured otherwise: indented lines, as in
+/// Java stack traces, and the lines that end a chained exception.
+pub const DEFAULT_CONTINUATIONS: [&str; 4] = [
+    r"^\s",
+    r"^#011",
+    r"^Caused by: ",
+    r"^\.\.\. \d+ (?:more|common frames omitted)",
+];
+/// Bounds memory when many senders are mid-event, further events aren't held back
+const MAX_PENDING_EVENTS: usize = 10_000;
+
+/// Sender, APP-NAME and PROCID, the lines of one event share all three
+type EventKey = (Option<String>, Option<String>, Option<String>);
+
+#[derive(Debug)]
+struct PendingEvent<T> {
+    packet: OwnedSyslogPacket,
+    origin: T,
+    last_seen: Instant,
+}
+
+/// Joins events that were split across consecutive messages, such as stack traces.
+///
+/// Every message is held back until the next message from the same sender and
+/// process shows whether more lines follow, or until it times out. Each event
+/// keeps the `T` its first line arrived with, e.g. where it was captured.
+#[derive(Debug)]
+pub struct Reassembler<T> {
+    continuations: Vec<Regex>,
+    timeout: Duration,
+    max_size: usize,
+    pending: HashMap<EventKey, PendingEvent<T>>,
+}
+
+impl<T> Reassembler<T> {
+    /// `continuations` match the MSG of lines that continue the previous message.
+    /// Events are complete after `timeout` without a new line, or once another
+    /// line would take them over `max_size` bytes.
+    pub fn new(continuations: Vec<Regex>, timeout: Duration, max_size: usize) -> Self {
+        Self {
+            continuations,
+            timeout,
+            max_size,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Adds a message, passing any event it completes to `emit`.
+    pub fn push(
+        &mut self,
+        packet: SyslogPacket<'_>,
+        origin: T,
+        now: Instant,
+        mut emit: impl FnMut(OwnedSyslogPacket, T),
+    ) {
+        let key = (
+            packet.hostname.as_deref().map(str::to_string),
+            packet.app_name.as_deref().map(str::to_string),
+            packet.procid.as_deref().map(str::to_string),
+        );
+        let continues = self.continuations.iter().any(|c| c.is_match(&packet.msg));
+
+        if let Some(pending) = self.pending.get_mut(&key) {
+            let size = pending.packet.message.len() + 1 + packet.msg.len();
+            if continues && size <= self.max_size {
+                append(&mut pending.packet, &packet.msg);
+                pending.last_seen = now;
+                return;
+            }
+            if let Some(done) = self.pending.remove(&key) {
+                emit(done.packet, done.origin);
+            }
+        }
+
+        if self.pending.len() >= MAX_PENDING_EVENTS {
+            emit(packet.into_owned(), origin);
+            return;
+        }
+        self.pending.insert(
+            key,
+            PendingEvent {
+                packet: packet.into_owned(),
+                origin,
+                last_seen: now,
+            },
+        );
+    }
+
+    /// Emits the events that saw no new line within the timeout.
+    pub fn flush_expired(&mut self, now: Instant, mut emit: impl FnMut(OwnedSyslogPacket, T)) {
+        let timeout = self.timeout;
+        let expired: Vec<EventKey> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.last_seen) >= timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            if let Some(done) = self.pending.remove(&key) {
+                emit(done.packet, done.origin);
+            }
+        }
+    }
+
+    /// Emits every pending event, e.g. before the final summary.
+    pub fn flush(&mut self, mut emit: impl FnMut(OwnedSyslogPacket, T)) {
+        for (_, done) in self.pending.drain() {
+            emit(done.packet, done.origin);
+        }
+    }
+}
+
+/// Adds a line to both the MSG and the full message.
+fn append(packet: &mut OwnedSyslogPacket, line: &str) {
+    packet.size += 1 + line.len();
+    for text in [&mut packet.message, &mut packet.msg] {
+        let text = text.to_mut();
+        text.push('\n');
+        text.push_str(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_syslog_packet;
+
+    fn reassembler(max_size: usize) -> Reassembler<()> {
+        let continuations = DEFAULT_CONTINUATIONS
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect();
+        Reassembler::new(continuations, Duration::from_secs(1), max_size)
+    }
+
+    fn push(reassembler: &mut Reassembler<()>, message: &str, now: Instant) -> Vec<String> {
+        let mut done = Vec::new();
+        let packet = parse_syslog_packet(message.as_bytes()).unwrap();
+        reassembler.push(packet, (), now, |packet, _| {
+            done.push(packet.msg.into_owned())
+        });
+        done
+    }
+
+    #[test]
+    fn test_join_stack_trace() {
+        let mut reassembler = reassembler(4096);
+        let now = Instant::now();
+        for line in [
+            "java.lang.IllegalStateException: boom",
+            "\tat com.example.Main.run(Main.java:10)",
+            "Caused by: java.io.IOException: disk",
+            "... 3 more",
+        ] {
+            let message = format!("<11>Oct 11 22:14:15 app01 java[42]: {line}");
+            assert!(push(&mut reassembler, &message, now).is_empty());
+        }
+        // Another process on the same host doesn't interrupt the event
+        assert!(push(
+            &mut reassembler,
+            "<11>Oct 11 22:14:15 app01 cron[7]: \tx",
+            now
+        )
+        .is_empty());
+
+        let done = push(
+            &mut reassembler,
+            "<14>Oct 11 22:14:16 app01 java[42]: next event",
+            now,
+        );
+        assert_eq!(
+            done,
+            vec![
+                "java.lang.IllegalStateException: boom\n\tat com.example.Main.run(Main.java:10)\n\
+                 Caused by: java.io.IOException: disk\n... 3 more"
+            ]
+        );
+
+        let mut flushed = Vec::new();
+        reassembler.flush(|packet, _| flushed.push(packet.msg.into_owned()));
+        flushed.sort();
+        assert_eq!(flushed, vec!["\tx", "next event"]);
+    }
+
+    #[test]
+    fn test_timeout_and_max_size() {
+        let mut reassembler = reassembler(60);
+        let start = Instant::now();
+        push(
+            &mut reassembler,
+            "<11>Oct 11 22:14:15 app01 java: first",
+            start,
+        );
+
+        let mut expired = Vec::new();
+        reassembler.flush_expired(start + Duration::from_millis(500), |p, _| {
+            expired.push(p.msg.into_owned())
+        });
+        assert!(expired.is_empty());
+        reassembler.flush_expired(start + Duration::from_secs(1), |p, _| {
+            expired.push(p.msg.into_owned())
+        });
+        assert_eq!(expired, vec!["first"]);
+
+        push(
+            &mut reassembler,
+            "<11>Oct 11 22:14:15 app01 java: second",
+            start,
+        );
+        let done = push(
+            &mut reassembler,
+            "<11>Oct 11 22:14:15 app01 java:  a continuation that is too long",
+            start,
+        );
+        assert_eq!(done, vec!["second"]);
+    }
+}
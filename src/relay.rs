@@ -0,0 +1,197 @@
+use crate::hostname::HostsFile;
+use crate::stats::count_capped;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Hosts whose messages a relay delivered.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RelayStats {
+    /// The relay's name from the hosts file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Messages per originating host
+    pub origins: HashMap<String, u64>,
+    /// Messages that arrived without a header hostname
+    #[serde(skip_serializing_if = "is_zero")]
+    pub missing_hostname: u64,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+struct SourceEntry {
+    address: String,
+    name: Option<String>,
+    origins: HashMap<String, u64>,
+    missing_hostname: u64,
+}
+
+impl SourceEntry {
+    /// Whether `origin` is how this source calls itself.
+    fn is_own_name(&self, origin: &str) -> bool {
+        origin == self.address || self.name.as_deref() == Some(origin)
+    }
+
+    /// A source that sends under names other than its own is forwarding for them.
+    /// Without a hosts file entry, a single foreign name is taken to be the
+    /// source's own, so only a source with several names counts as a relay.
+    fn is_relay(&self) -> bool {
+        let foreign = self
+            .origins
+            .keys()
+            .filter(|origin| !self.is_own_name(origin))
+            .count();
+        foreign > 1 || (foreign == 1 && self.name.is_some())
+    }
+}
+
+/// Compares header hostnames with the address packets actually came from.
+#[derive(Default)]
+pub struct RelayTracker {
+    hosts: Option<HostsFile>,
+    sources: HashMap<IpAddr, SourceEntry>,
+    /// Caps the sources and the origins per source, see [`Self::set_max_entries`]
+    max_entries: Option<usize>,
+}
+
+impl RelayTracker {
+    /// `hosts` names the sources, so a relay forwarding for one host is recognised.
+    pub fn new(hosts: Option<HostsFile>) -> Self {
+        Self {
+            hosts,
+            sources: HashMap::new(),
+            max_entries: None,
+        }
+    }
+
+    /// Tracks at most `max` sources, ignoring new ones beyond, and counts origins
+    /// past `max` per source as [`OTHER_VALUES`](crate::stats::OTHER_VALUES).
+    pub fn set_max_entries(&mut self, max: Option<usize>) {
+        self.max_entries = max;
+    }
+
+    /// Records a message from `source`. `header_hostname` is the hostname as sent,
+    /// `hostname` the key the message is tracked under.
+    pub fn record(&mut self, source: IpAddr, header_hostname: Option<&str>, hostname: &str) {
+        let max = self.max_entries.unwrap_or(usize::MAX);
+        if self.sources.len() >= max && !self.sources.contains_key(&source) {
+            return;
+        }
+        let hosts = &self.hosts;
+        let entry = self.sources.entry(source).or_insert_with(|| {
+            let address = source.to_string();
+            SourceEntry {
+                name: hosts
+                    .as_ref()
+                    .and_then(|hosts| hosts.resolve(&address))
+                    .map(str::to_string),
+                address,
+                origins: HashMap::new(),
+                missing_hostname: 0,
+            }
+        });
+
+        if matches!(header_hostname, None | Some("" | "-")) {
+            entry.missing_hostname += 1;
+        } else {
+            count_capped(&mut entry.origins, hostname, max);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.sources.clear();
+    }
+
+    /// Relays by address, with the hosts they delivered for.
+    pub fn relays(&self) -> HashMap<String, RelayStats> {
+        self.sources
+            .values()
+            .filter(|entry| entry.is_relay())
+            .map(|entry| {
+                let origins = entry
+                    .origins
+                    .iter()
+                    .filter(|(origin, _)| !entry.is_own_name(origin))
+                    .map(|(origin, count)| (origin.clone(), *count))
+                    .collect();
+                let stats = RelayStats {
+                    name: entry.name.clone(),
+                    origins,
+                    missing_hostname: entry.missing_hostname,
+                };
+                (entry.address.clone(), stats)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::OTHER_VALUES;
+
+    const RELAY: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 5));
+    const DIRECT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 7));
+
+    #[test]
+    fn test_relay_detection() {
+        let mut tracker = RelayTracker::new(None);
+        for host in ["web01", "web01", "web02"] {
+            tracker.record(RELAY, Some(host), host);
+        }
+        tracker.record(RELAY, Some("-"), "10.0.0.5");
+        tracker.record(RELAY, None, "Unknown");
+        tracker.record(DIRECT, Some("db01"), "db01");
+        tracker.record(DIRECT, None, "10.0.0.7");
+
+        let relays = tracker.relays();
+        assert_eq!(relays.len(), 1);
+        let relay = &relays["10.0.0.5"];
+        assert_eq!(relay.origins["web01"], 2);
+        assert_eq!(relay.origins["web02"], 1);
+        assert_eq!(relay.missing_hostname, 2);
+        assert_eq!(relay.name, None);
+
+        tracker.clear();
+        assert!(tracker.relays().is_empty());
+    }
+
+    #[test]
+    fn test_relay_detection_with_hosts_file() {
+        let hosts = HostsFile::parse("10.0.0.5 relay01\n10.0.0.7 db01\n");
+        let mut tracker = RelayTracker::new(Some(hosts));
+        tracker.record(RELAY, Some("relay01"), "relay01");
+        tracker.record(RELAY, Some("web01"), "web01");
+        tracker.record(DIRECT, Some("db01"), "db01");
+        tracker.record(DIRECT, Some("10.0.0.7"), "10.0.0.7");
+
+        let relays = tracker.relays();
+        assert_eq!(relays.len(), 1);
+        let relay = &relays["10.0.0.5"];
+        assert_eq!(relay.name.as_deref(), Some("relay01"));
+        assert_eq!(relay.origins, HashMap::from([("web01".to_string(), 1)]));
+
+        let json = serde_json::to_string(relay).unwrap();
+        assert!(!json.contains("missing_hostname"));
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut tracker = RelayTracker::new(None);
+        tracker.set_max_entries(Some(2));
+        for host in ["web01", "web02", "web03", "web04", "web01"] {
+            tracker.record(RELAY, Some(host), host);
+        }
+        tracker.record(DIRECT, Some("db01"), "db01");
+        tracker.record(IpAddr::from([10, 0, 0, 9]), Some("x"), "x");
+
+        assert_eq!(tracker.sources.len(), 2);
+        let relays = tracker.relays();
+        let origins = &relays["10.0.0.5"].origins;
+        assert_eq!(origins.len(), 3);
+        assert_eq!(origins["web01"], 2);
+        assert_eq!(origins[OTHER_VALUES], 2);
+    }
+}
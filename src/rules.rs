@@ -0,0 +1,313 @@
+use crate::grok::GrokLibrary;
+use crate::SyslogPacket;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What a rule's pattern is matched against.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RuleInput {
+    /// The MSG part, after the header
+    #[default]
+    Msg,
+    /// The whole message without its `<PRI>`, as Logstash sees it
+    Message,
+}
+
+/// A rules file with its own grok patterns, instead of a bare list of rules.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    /// Logstash-style pattern files, relative to the rules file
+    #[serde(default)]
+    pattern_files: Vec<String>,
+    #[serde(default)]
+    patterns: HashMap<String, String>,
+    rules: Vec<RuleSpec>,
+}
+
+/// A rule as written in the rules file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    name: String,
+    /// A regex, mutually exclusive with `grok`
+    pattern: Option<String>,
+    /// A grok expression such as `%{SYSLOGBASE} %{GREEDYDATA:message}`
+    grok: Option<String>,
+    #[serde(default)]
+    input: RuleInput,
+    /// Host globs, e.g. `web*`. Empty matches every host.
+    #[serde(default)]
+    hosts: Vec<String>,
+    /// APP-NAME or TAG globs. Empty matches every app.
+    #[serde(default)]
+    apps: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    pattern: Regex,
+    input: RuleInput,
+    hosts: Option<Regex>,
+    apps: Option<Regex>,
+}
+
+impl Rule {
+    fn compile(spec: RuleSpec, grok: &GrokLibrary) -> Result<Self, String> {
+        let pattern = match (&spec.pattern, &spec.grok) {
+            (Some(pattern), None) => Regex::new(pattern).map_err(|e| e.to_string()),
+            (None, Some(expression)) => grok.compile(expression),
+            _ => Err("needs exactly one of pattern and grok".to_string()),
+        }
+        .map_err(|e| format!("Invalid pattern in rule {}: {}", spec.name, e))?;
+        if pattern.capture_names().flatten().next().is_none() {
+            return Err(format!("Rule {} has no named capture groups", spec.name));
+        }
+        Ok(Self {
+            name: spec.name,
+            pattern,
+            input: spec.input,
+            hosts: globs(&spec.hosts),
+            apps: globs(&spec.apps),
+        })
+    }
+
+    fn applies_to(&self, hostname: &str, app: Option<&str>) -> bool {
+        self.hosts
+            .as_ref()
+            .is_none_or(|hosts| hosts.is_match(hostname))
+            && self
+                .apps
+                .as_ref()
+                .is_none_or(|apps| app.is_some_and(|app| apps.is_match(app)))
+    }
+}
+
+/// Compiles `*` globs into one case-insensitive, anchored regex.
+fn globs(globs: &[String]) -> Option<Regex> {
+    if globs.is_empty() {
+        return None;
+    }
+    let alternatives: Vec<String> = globs
+        .iter()
+        .map(|glob| regex::escape(glob).replace(r"\*", ".*"))
+        .collect();
+    // Escaped literals joined by `|` always form a valid regex
+    Regex::new(&format!("(?i)^(?:{})$", alternatives.join("|"))).ok()
+}
+
+/// The fields a [`RuleSet`] extracted from one message.
+#[derive(Debug, Default, PartialEq)]
+pub struct Extraction<'r> {
+    pub fields: Vec<(String, String)>,
+    /// Names of the rules that matched
+    pub rules: Vec<&'r str>,
+}
+
+/// User-defined regex and grok rules that extract named fields from messages.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read rules file {}: {}", path, e))?;
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        Self::parse_in(&contents, base)
+    }
+
+    /// Parses a JSON array of rules:
+    /// `[{"name": "...", "pattern": "(?P<field>...)", "hosts": ["web*"], "apps": ["nginx"]}]`
+    ///
+    /// Rules may use `grok` instead of `pattern`. To add grok patterns, wrap the list
+    /// in `{"pattern_files": [...], "patterns": {"NAME": "..."}, "rules": [...]}`.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        Self::parse_in(json, Path::new(""))
+    }
+
+    fn parse_in(json: &str, base: &Path) -> Result<Self, String> {
+        let invalid = |e: serde_json::Error| format!("Invalid rules file: {}", e);
+        let value: serde_json::Value = serde_json::from_str(json).map_err(invalid)?;
+        let file = if value.is_array() {
+            RulesFile {
+                pattern_files: Vec::new(),
+                patterns: HashMap::new(),
+                rules: serde_json::from_value(value).map_err(invalid)?,
+            }
+        } else {
+            serde_json::from_value(value).map_err(invalid)?
+        };
+
+        let mut grok = GrokLibrary::new();
+        for path in &file.pattern_files {
+            grok.load_patterns(&base.join(path))?;
+        }
+        for (name, pattern) in &file.patterns {
+            grok.add_patterns(&format!("{} {}", name, pattern));
+        }
+
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|spec| Rule::compile(spec, &grok))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Runs every rule that applies to the host and app and returns the named
+    /// groups that matched, in rule order.
+    pub fn extract(&self, hostname: &str, packet: &SyslogPacket<'_>) -> Extraction<'_> {
+        let mut extraction = Extraction::default();
+        let app = packet.app_name.as_deref();
+        for rule in self.rules.iter().filter(|r| r.applies_to(hostname, app)) {
+            let input = match rule.input {
+                RuleInput::Msg => &packet.msg,
+                RuleInput::Message => without_pri(&packet.message),
+            };
+            let Some(captures) = rule.pattern.captures(input) else {
+                continue;
+            };
+            extraction.rules.push(&rule.name);
+            for name in rule.pattern.capture_names().flatten() {
+                if let Some(value) = captures.name(name) {
+                    extraction
+                        .fields
+                        .push((name.to_string(), value.as_str().to_string()));
+                }
+            }
+        }
+        extraction
+    }
+}
+
+fn without_pri(message: &str) -> &str {
+    message
+        .strip_prefix('<')
+        .and_then(|rest| rest.split_once('>'))
+        .filter(|(pri, _)| (1..=3).contains(&pri.len()) && pri.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(message, |(_, rest)| rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_syslog_packet;
+
+    const RULES: &str = r#"[
+        {"name": "status", "pattern": "status=(?P<status>\\d+)( bytes=(?P<bytes>\\d+))?", "hosts": ["web*"]},
+        {"name": "ssh", "pattern": "^(?P<outcome>Accepted|Failed) \\S+ for (?P<user>\\S+)", "apps": ["sshd"]}
+    ]"#;
+
+    fn extract(rules: &RuleSet, hostname: &str, message: &str) -> Vec<(String, String)> {
+        let packet = parse_syslog_packet(message.as_bytes()).unwrap();
+        rules.extract(hostname, &packet).fields
+    }
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_extract() {
+        let rules = RuleSet::parse(RULES).unwrap();
+        assert!(!rules.is_empty());
+
+        let message = "<13>Oct 11 22:14:15 web01 nginx: GET / status=404";
+        assert_eq!(
+            extract(&rules, "WEB01", message),
+            vec![pair("status", "404")]
+        );
+        assert!(extract(&rules, "db01", message).is_empty());
+
+        let message = "<13>Oct 11 22:14:15 bastion sshd[1]: Failed password for root";
+        assert_eq!(
+            extract(&rules, "bastion", message),
+            vec![pair("outcome", "Failed"), pair("user", "root")]
+        );
+        let message = "<13>Oct 11 22:14:15 bastion login: Failed password for root";
+        assert!(extract(&rules, "bastion", message).is_empty());
+
+        assert!(RuleSet::default().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (json, error) in [
+            ("not json", "Invalid rules file"),
+            (
+                r#"[{"name": "x", "pattern": "("}]"#,
+                "Invalid pattern in rule x",
+            ),
+            (
+                r#"[{"name": "x", "pattern": "\\d+"}]"#,
+                "no named capture groups",
+            ),
+            (
+                r#"[{"name": "x", "pattern": "(?P<a>.)", "host": []}]"#,
+                "unknown field",
+            ),
+            (
+                r#"[{"name": "x", "pattern": "(?P<a>.)", "grok": "%{INT:a}"}]"#,
+                "exactly one of pattern and grok",
+            ),
+            (
+                r#"[{"name": "x", "grok": "%{NOPE:a}"}]"#,
+                "Unknown grok pattern",
+            ),
+            (
+                r#"[{"name": "x", "grok": "%{SYSLOGBASE}"}]"#,
+                "no named capture groups",
+            ),
+            (r#"{"rules": [], "bogus": 1}"#, "unknown field"),
+            (
+                r#"{"pattern_files": ["/nonexistent"], "rules": []}"#,
+                "Failed to read grok patterns",
+            ),
+        ] {
+            let result = RuleSet::parse(json);
+            assert!(result.unwrap_err().contains(error), "{json}");
+        }
+        assert!(RuleSet::load("/nonexistent/rules.json").is_err());
+    }
+
+    #[test]
+    fn test_grok_rules() {
+        let dir = std::env::temp_dir().join(format!("syslog_sniffer_grok_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("extra"), "TICKET [A-Z]+-%{INT}\n").unwrap();
+        let rules_path = dir.join("rules.json");
+        let json = r#"{
+            "pattern_files": ["extra"],
+            "patterns": {"ACTION": "opened|closed"},
+            "rules": [
+                {"name": "base", "grok": "^%{SYSLOGTIMESTAMP} %{SYSLOGHOST:logsource} %{SYSLOGPROG}", "input": "message"},
+                {"name": "ticket", "grok": "%{TICKET:ticket} %{ACTION:action}"},
+                {"name": "never", "grok": "%{UUID:uuid}"}
+            ]
+        }"#;
+        std::fs::write(&rules_path, json).unwrap();
+        let rules = RuleSet::load(rules_path.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        let rules = rules.unwrap();
+
+        let message = b"<13>Oct 11 22:14:15 jira01 tracker[7]: OPS-12 closed";
+        let packet = parse_syslog_packet(message).unwrap();
+        let extraction = rules.extract("jira01", &packet);
+        assert_eq!(extraction.rules, vec!["base", "ticket"]);
+        assert!(extraction.fields.contains(&pair("logsource", "jira01")));
+        // Only fields the rule names are extracted, not those inside SYSLOGPROG
+        assert!(!extraction.fields.iter().any(|(name, _)| name == "pid"));
+        assert!(extraction.fields.contains(&pair("ticket", "OPS-12")));
+        assert!(extraction.fields.contains(&pair("action", "closed")));
+    }
+}
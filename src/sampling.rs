@@ -0,0 +1,202 @@
+use crate::pri::SEVERITIES;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+
+/// Stratum of messages without a severity
+const NO_SEVERITY: &str = "-";
+
+/// How many samples to keep per host and how.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SampleConfig {
+    /// Samples per host, or per host and severity when stratified. 0 keeps none.
+    pub count: usize,
+    pub by_severity: bool,
+    /// Longer samples are cut, ending in a marker with the number of bytes left out
+    pub max_length: Option<usize>,
+}
+
+/// Cuts `message` to at most `max_length` bytes plus a marker, on a character boundary.
+pub fn truncate(message: &str, max_length: Option<usize>) -> String {
+    let Some(max_length) = max_length.filter(|&max| message.len() > max) else {
+        return message.to_string();
+    };
+    let mut end = max_length;
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}... [{} bytes truncated]",
+        &message[..end],
+        message.len() - end
+    )
+}
+
+/// A uniform random sample of the messages added, after Vitter's Algorithm R.
+#[derive(Debug, Clone)]
+struct Reservoir {
+    seen: u64,
+    samples: Vec<String>,
+}
+
+/// xorshift64*, good enough to pick samples and seeded randomly per host
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) % bound
+    }
+}
+
+/// Per-host samples, one reservoir per severity when stratified.
+#[derive(Debug, Clone)]
+pub struct Samples {
+    config: SampleConfig,
+    /// Indexed by severity code, the last one holds messages without a severity
+    reservoirs: Vec<Reservoir>,
+    rng: Rng,
+}
+
+impl Samples {
+    pub fn new(config: SampleConfig) -> Self {
+        let strata = if config.by_severity {
+            SEVERITIES.len() + 1
+        } else {
+            1
+        };
+        let reservoir = Reservoir {
+            seen: 0,
+            samples: Vec::new(),
+        };
+        Self {
+            config,
+            reservoirs: vec![reservoir; if config.count > 0 { strata } else { 0 }],
+            rng: Rng::new(),
+        }
+    }
+
+    /// Offers a message, only copying it if it is picked.
+    pub fn add(&mut self, message: &str, severity: Option<u8>) {
+        let index = match severity {
+            Some(severity) if self.config.by_severity => {
+                usize::from(severity).min(SEVERITIES.len())
+            }
+            _ if self.config.by_severity => SEVERITIES.len(),
+            _ => 0,
+        };
+        let Some(reservoir) = self.reservoirs.get_mut(index) else {
+            return;
+        };
+        reservoir.seen += 1;
+        if reservoir.samples.len() < self.config.count {
+            reservoir
+                .samples
+                .push(truncate(message, self.config.max_length));
+            return;
+        }
+        let slot = self.rng.below(reservoir.seen) as usize;
+        if let Some(sample) = reservoir.samples.get_mut(slot) {
+            *sample = truncate(message, self.config.max_length);
+        }
+    }
+
+    /// The samples, unless stratified.
+    pub fn samples(&self) -> Vec<String> {
+        match self.reservoirs.as_slice() {
+            [reservoir] => reservoir.samples.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Samples per severity keyword when stratified, `-` for messages without one.
+    pub fn by_severity(&self) -> HashMap<&'static str, Vec<String>> {
+        if self.reservoirs.len() < 2 {
+            return HashMap::new();
+        }
+        SEVERITIES
+            .iter()
+            .chain([&NO_SEVERITY])
+            .zip(&self.reservoirs)
+            .filter(|(_, reservoir)| !reservoir.samples.is_empty())
+            .map(|(severity, reservoir)| (*severity, reservoir.samples.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", None), "hello");
+        assert_eq!(truncate("hello", Some(5)), "hello");
+        assert_eq!(
+            truncate("hello world", Some(5)),
+            "hello... [6 bytes truncated]"
+        );
+        // Never splits a character
+        assert_eq!(truncate("héllo", Some(2)), "h... [5 bytes truncated]");
+    }
+
+    #[test]
+    fn test_reservoir_is_uniform() {
+        let config = SampleConfig {
+            count: 10,
+            ..SampleConfig::default()
+        };
+        let mut picked_late = 0;
+        for _ in 0..100 {
+            let mut samples = Samples::new(config);
+            for i in 0..1000 {
+                samples.add(&i.to_string(), None);
+            }
+            let samples = samples.samples();
+            assert_eq!(samples.len(), 10);
+            picked_late += samples
+                .iter()
+                .filter(|sample| sample.parse::<u32>().unwrap() >= 500)
+                .count();
+        }
+        // Half of the 1000 samples taken should come from the second half
+        assert!((400..=600).contains(&picked_late), "{picked_late}");
+    }
+
+    #[test]
+    fn test_stratified() {
+        let config = SampleConfig {
+            count: 2,
+            by_severity: true,
+            max_length: Some(4),
+        };
+        let mut samples = Samples::new(config);
+        for (message, severity) in [
+            ("err one", Some(3)),
+            ("info", Some(6)),
+            ("info", Some(6)),
+            ("info", Some(6)),
+            ("raw", None),
+        ] {
+            samples.add(message, severity);
+        }
+        assert!(samples.samples().is_empty());
+        let by_severity = samples.by_severity();
+        assert_eq!(by_severity["err"], vec!["err ... [3 bytes truncated]"]);
+        assert_eq!(by_severity["info"], vec!["info", "info"]);
+        assert_eq!(by_severity["-"], vec!["raw"]);
+        assert_eq!(by_severity.len(), 3);
+
+        let mut samples = Samples::new(SampleConfig::default());
+        samples.add("x", None);
+        assert!(samples.samples().is_empty());
+        assert!(samples.by_severity().is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+/// Each power of two is split into `2^SUB_BUCKET_BITS` buckets, which bounds the
+/// relative error of a percentile to 1/16
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+/// Sizes from 16 MiB up share the last bucket, far beyond any syslog message
+const MAX_OCTAVE: u32 = 23;
+const BUCKETS: usize = ((MAX_OCTAVE - SUB_BUCKET_BITS + 2) * SUB_BUCKETS as u32) as usize;
+
+/// Message sizes in bytes over an interval.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct SizeStats {
+    pub min: u64,
+    pub avg: u64,
+    pub max: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+/// Log-linear histogram of message sizes, as in HdrHistogram: sizes below 16 are
+/// counted exactly, larger ones in buckets 1/16 of their power of two wide. Uses a
+/// fixed 2.7 KB whatever the traffic.
+#[derive(Debug, Clone)]
+pub struct SizeSketch {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    bytes: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for SizeSketch {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            bytes: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+fn bucket(size: u64) -> usize {
+    if size < SUB_BUCKETS {
+        return size as usize;
+    }
+    let octave = (63 - size.leading_zeros()).min(MAX_OCTAVE);
+    let size = size.min((1 << (MAX_OCTAVE + 1)) - 1);
+    let sub = (size >> (octave - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    ((u64::from(octave - SUB_BUCKET_BITS + 1)) * SUB_BUCKETS + sub) as usize
+}
+
+/// Smallest size in a bucket and the bucket's width.
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return (index, 1);
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    ((SUB_BUCKETS + index % SUB_BUCKETS) << shift, 1 << shift)
+}
+
+impl SizeSketch {
+    pub fn add(&mut self, size: usize) {
+        let size = size as u64;
+        self.buckets[bucket(size)] += 1;
+        self.count += 1;
+        self.bytes += size;
+        self.min = self.min.min(size);
+        self.max = self.max.max(size);
+    }
+
+    /// Total bytes added.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The size below which a share `q` of messages fall, within 1/16 of the exact value.
+    pub fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((q * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let (low, width) = bucket_range(index);
+                return (low + (width - 1) / 2).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn stats(&self) -> SizeStats {
+        if self.count == 0 {
+            return SizeStats::default();
+        }
+        SizeStats {
+            min: self.min,
+            avg: (self.bytes + self.count / 2) / self.count,
+            max: self.max,
+            p50: self.quantile(0.5),
+            p95: self.quantile(0.95),
+            p99: self.quantile(0.99),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        for size in [0, 15, 16, 17, 40, 1000, 65535, 1 << 23, (1 << 24) - 1] {
+            let (low, width) = bucket_range(bucket(size));
+            assert!(low <= size && size < low + width, "{size}: {low}+{width}");
+        }
+        assert_eq!(bucket(u64::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut sketch = SizeSketch::default();
+        assert_eq!(sketch.stats(), SizeStats::default());
+        for size in 1..=1000 {
+            sketch.add(size);
+        }
+        let stats = sketch.stats();
+        assert_eq!((stats.min, stats.avg, stats.max), (1, 501, 1000));
+        assert_eq!(sketch.bytes(), 500_500);
+        for (estimate, exact) in [(stats.p50, 500.0), (stats.p95, 950.0), (stats.p99, 990.0)] {
+            assert!(
+                (estimate as f64 - exact).abs() / exact <= 1.0 / 16.0,
+                "{estimate}"
+            );
+        }
+
+        let mut sketch = SizeSketch::default();
+        sketch.add(300);
+        assert_eq!(sketch.stats().p99, 300);
+    }
+}
@@ -1,20 +1,350 @@
+use crate::auth::{AuthEvent, AuthStats};
+use crate::cardinality::HyperLogLog;
+use crate::compliance::Violation;
+use crate::escapes::Escape;
+use crate::events::Event;
+use crate::grouping::{GroupKey, GroupOutput, GroupTracker, Origin};
+use crate::hostname::HostsFile;
+use crate::parser::parse_header;
+use crate::pri::{FACILITIES, SEVERITIES};
+use crate::rate::{per_second, PeakRate};
+use crate::relay::{RelayStats, RelayTracker};
+use crate::sampling::{truncate, SampleConfig, Samples};
+use crate::sizes::{SizeSketch, SizeStats};
+use crate::structured::pointer_value;
+use crate::templates::{template_hash, TemplateMiner, TemplateStats, MAX_CLUSTERS};
+use crate::{MessageFormat, SyslogPacket};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::net::IpAddr;
+use std::time::SystemTime;
 
 #[derive(Serialize)]
 pub struct JsonSummary {
     pub interval_seconds: u64,
+    pub totals: FleetTotals,
     pub hosts: HashMap<String, HostStats>,
+    /// Sources that forward messages for other hosts, keyed by address
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub relays: HashMap<String, RelayStats>,
+    /// Authentication outcomes per remote address, to spot brute-force sources
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub auth_sources: HashMap<String, AuthStats>,
+    /// Message counts per combination of the `--group-by` keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<serde_json::Value>,
+    /// Hosts evicted to stay within `--max-hosts`, and their messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other: Option<OtherStats>,
 }
 
 #[derive(Serialize, Clone)]
 pub struct HostStats {
     pub count: u64,
+    /// With a host budget, how many more messages the host may have sent before it
+    /// was tracked. Its true count lies between `count` and `count + count_error`.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub count_error: u64,
+    /// Total size of the messages as received
+    pub bytes: u64,
+    /// Message sizes in bytes, percentiles are within 1/16 of the exact value
+    pub size: SizeStats,
+    /// Average messages per second over the interval
+    pub rate: f64,
+    /// Messages in the busiest second, by capture time
+    #[serde(skip_serializing_if = "is_zero")]
+    pub peak_rate: u64,
+    /// Unix time of the busiest second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_at: Option<u64>,
+    /// Estimated number of distinct MSGs, within about 3%
+    pub distinct_messages: u64,
+    /// Estimated number of distinct templates among the MSGs, as mined. Without
+    /// mining, or past its budget, MSGs with tokens containing digits masked count.
+    pub distinct_templates: u64,
+    /// The host's first message
     pub sample: String,
+    /// A uniform random sample of the host's messages, with `--samples`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub samples: Vec<String>,
+    /// Random samples per severity keyword, with `--samples-by-severity`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub severity_samples: HashMap<&'static str, Vec<String>>,
+    /// Per JSON pointer or extracted field, the number of messages seen with each value.
+    /// Values beyond the first [`MAX_FIELD_VALUES`] are counted as [`OTHER_VALUES`].
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, HashMap<String, u64>>,
+    /// Messages per extraction rule that matched them
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub rules: HashMap<String, u64>,
+    /// Messages no extraction rule matched, only counted when rules are configured
+    #[serde(skip_serializing_if = "is_zero")]
+    pub rules_unmatched: u64,
+    /// Messages per APP-NAME or RFC 3164 TAG
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub apps: HashMap<String, u64>,
+    /// Messages per wire format, e.g. `rfc3164` or `gelf`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub formats: HashMap<MessageFormat, u64>,
+    /// Messages per severity keyword, e.g. `err` or `debug`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub severities: HashMap<&'static str, u64>,
+    /// Messages per facility keyword, e.g. `auth` or `local0`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub facilities: HashMap<&'static str, u64>,
+    /// The most common message templates, most frequent first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<TemplateStats>,
+    /// The most frequently blocked source/destination pairs in netfilter logs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked: Vec<FlowStats>,
+    /// Messages per auditd record type, e.g. `SYSCALL` or `USER_LOGIN`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub audit_types: HashMap<String, u64>,
+    /// Messages per Windows EventID
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub windows_events: HashMap<u32, u64>,
+    /// sshd, sudo and PAM outcomes
+    #[serde(skip_serializing_if = "AuthStats::is_empty")]
+    pub auth: AuthStats,
+    /// Messages per encoding of control characters, e.g. rsyslog's `#012`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub escapes: HashMap<Escape, u64>,
+    /// Messages per RFC violation, only collected in strict mode
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub violations: HashMap<Violation, u64>,
+    /// Messages per relay address this host's messages arrived through
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub via: HashMap<String, u64>,
+}
+
+/// Counts over all hosts.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct FleetTotals {
+    pub count: u64,
+    pub bytes: u64,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub severities: HashMap<&'static str, u64>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub facilities: HashMap<&'static str, u64>,
+}
+
+/// What was counted for hosts no longer tracked individually.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct OtherStats {
+    /// Evictions, a host evicted twice is counted twice
+    pub hosts: u64,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FlowStats {
+    pub src: String,
+    pub dst: String,
+    pub count: u64,
+}
+
+/// Templates reported per host unless configured otherwise
+pub const DEFAULT_TOP_TEMPLATES: usize = 5;
+/// Blocked source/destination pairs reported per host
+const TOP_BLOCKED: usize = 10;
+/// Distinct values counted per host and field, later values share [`OTHER_VALUES`].
+/// Also caps the apps, blocked flows and audit record types per host.
+pub const MAX_FIELD_VALUES: usize = 100;
+/// Key under which values beyond a cap are counted
+pub const OTHER_VALUES: &str = "(other)";
+/// Templates mined over all hosts under a host budget, shared out evenly but at
+/// least [`MIN_HOST_TEMPLATES`] per host
+const TEMPLATE_BUDGET: usize = 100_000;
+const MIN_HOST_TEMPLATES: usize = 10;
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+/// Keyword to count, leaving out keywords that weren't seen.
+fn histogram(counts: &[u64], names: &[&'static str]) -> HashMap<&'static str, u64> {
+    names
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| **count > 0)
+        .map(|(name, count)| (*name, *count))
+        .collect()
+}
+
+/// Counts `key`, only copying it the first time it is seen.
+fn increment(counts: &mut HashMap<String, u64>, key: &str) {
+    match counts.get_mut(key) {
+        Some(count) => *count += 1,
+        None => {
+            counts.insert(key.to_string(), 1);
+        }
+    }
+}
+
+/// `key`, or [`OTHER_VALUES`] if it is new and `map` already has `max` keys, so values
+/// such as timestamps or spoofed names can't grow a map without bound.
+pub(crate) fn capped_key<'a, V>(map: &HashMap<String, V>, key: &'a str, max: usize) -> &'a str {
+    if map.len() < max || map.contains_key(key) {
+        key
+    } else {
+        OTHER_VALUES
+    }
+}
+
+/// Counts `key`, as [`OTHER_VALUES`] once there are `max` distinct keys.
+pub(crate) fn count_capped(counts: &mut HashMap<String, u64>, key: &str, max: usize) {
+    increment(counts, capped_key(counts, key, max));
+}
+
+struct HostEntry {
+    count: u64,
+    count_error: u64,
+    sizes: SizeSketch,
+    peak_rate: PeakRate,
+    distinct_messages: HyperLogLog,
+    distinct_templates: HyperLogLog,
+    sample: String,
+    samples: Samples,
+    fields: HashMap<String, HashMap<String, u64>>,
+    rules: HashMap<String, u64>,
+    rules_unmatched: u64,
+    apps: HashMap<String, u64>,
+    formats: HashMap<MessageFormat, u64>,
+    /// Indexed by severity and facility code
+    severities: [u64; SEVERITIES.len()],
+    facilities: [u64; FACILITIES.len()],
+    templates: TemplateMiner,
+    /// Blocked packets per source, then destination
+    blocked: HashMap<String, HashMap<String, u64>>,
+    audit_types: HashMap<String, u64>,
+    windows_events: HashMap<u32, u64>,
+    auth: AuthStats,
+    escapes: HashMap<Escape, u64>,
+    violations: HashMap<Violation, u64>,
+}
+
+impl HostEntry {
+    fn new(sample: String, sampling: SampleConfig, max_templates: usize) -> Self {
+        Self {
+            count: 0,
+            count_error: 0,
+            sizes: SizeSketch::default(),
+            peak_rate: PeakRate::default(),
+            distinct_messages: HyperLogLog::default(),
+            distinct_templates: HyperLogLog::default(),
+            sample,
+            samples: Samples::new(sampling),
+            fields: HashMap::new(),
+            rules: HashMap::new(),
+            rules_unmatched: 0,
+            apps: HashMap::new(),
+            formats: HashMap::new(),
+            severities: [0; SEVERITIES.len()],
+            facilities: [0; FACILITIES.len()],
+            templates: TemplateMiner::with_max_clusters(max_templates),
+            blocked: HashMap::new(),
+            audit_types: HashMap::new(),
+            windows_events: HashMap::new(),
+            auth: AuthStats::default(),
+            escapes: HashMap::new(),
+            violations: HashMap::new(),
+        }
+    }
+
+    /// Counts a MSG towards the distinct estimates, mining its template if `mine`.
+    fn add_msg(&mut self, msg: &str, mine: bool) {
+        self.distinct_messages.add(msg);
+        match mine.then(|| self.templates.add(msg)).flatten() {
+            Some(template) => self.distinct_templates.add(template),
+            // Masking digits stands in for templates that weren't mined
+            None => self.distinct_templates.add_hash(template_hash(msg)),
+        }
+    }
+
+    fn add_event(&mut self, event: &Event) {
+        match event {
+            Event::Netfilter(netfilter) if netfilter.blocked => {
+                let src = capped_key(&self.blocked, &netfilter.src, MAX_FIELD_VALUES);
+                if !self.blocked.contains_key(src) {
+                    self.blocked.insert(src.to_string(), HashMap::new());
+                }
+                if let Some(destinations) = self.blocked.get_mut(src) {
+                    count_capped(destinations, &netfilter.dst, MAX_FIELD_VALUES);
+                }
+            }
+            Event::Netfilter(_) => {}
+            Event::Audit(audit) => {
+                count_capped(&mut self.audit_types, &audit.record_type, MAX_FIELD_VALUES);
+            }
+            Event::Auth(auth) => self.auth.record(auth),
+            Event::Windows(windows) => {
+                *self.windows_events.entry(windows.event_id).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn top_blocked(&self) -> Vec<FlowStats> {
+        let mut flows: Vec<FlowStats> = self
+            .blocked
+            .iter()
+            .flat_map(|(src, destinations)| {
+                destinations.iter().map(|(dst, count)| FlowStats {
+                    src: src.clone(),
+                    dst: dst.clone(),
+                    count: *count,
+                })
+            })
+            .collect();
+        flows.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| (&a.src, &a.dst).cmp(&(&b.src, &b.dst)))
+        });
+        flows.truncate(TOP_BLOCKED);
+        flows
+    }
+}
+
+/// Totals of evicted hosts, kept so fleet totals stay exact.
+#[derive(Default)]
+struct Evicted {
+    stats: OtherStats,
+    severities: [u64; SEVERITIES.len()],
+    facilities: [u64; FACILITIES.len()],
+}
+
+impl Evicted {
+    fn add(&mut self, entry: &HostEntry) {
+        self.stats.hosts += 1;
+        self.stats.count += entry.count;
+        self.stats.bytes += entry.sizes.bytes();
+        for (total, count) in self.severities.iter_mut().zip(entry.severities) {
+            *total += count;
+        }
+        for (total, count) in self.facilities.iter_mut().zip(entry.facilities) {
+            *total += count;
+        }
+    }
 }
 
 pub struct StatsTracker {
-    stats: HashMap<String, (u64, String)>,
+    stats: HashMap<String, HostEntry>,
+    max_hosts: Option<usize>,
+    /// With a host budget, each tracked host with a lower bound of its estimated
+    /// count, smallest first. Bounds are refreshed lazily when they reach the top.
+    by_count: BinaryHeap<Reverse<(u64, String)>>,
+    evicted: Evicted,
+    sampling: SampleConfig,
+    json_fields: Vec<String>,
+    relays: RelayTracker,
+    auth_sources: HashMap<String, AuthStats>,
+    groups: GroupTracker,
+    top_templates: usize,
+    /// Templates mined per host
+    max_templates: usize,
 }
 
 impl Default for StatsTracker {
@@ -25,16 +355,234 @@ impl Default for StatsTracker {
 
 impl StatsTracker {
     pub fn new() -> Self {
+        Self::with_json_fields(Vec::new())
+    }
+
+    /// Creates a tracker that breaks each host down by the given JSON pointers
+    /// into the structured payload, e.g. `/user` or `/event/outcome`.
+    pub fn with_json_fields(json_fields: Vec<String>) -> Self {
         Self {
             stats: HashMap::new(),
+            max_hosts: None,
+            by_count: BinaryHeap::new(),
+            evicted: Evicted::default(),
+            sampling: SampleConfig::default(),
+            json_fields,
+            relays: RelayTracker::default(),
+            auth_sources: HashMap::new(),
+            groups: GroupTracker::default(),
+            top_templates: DEFAULT_TOP_TEMPLATES,
+            max_templates: MAX_CLUSTERS,
         }
     }
 
-    pub fn add_entry(&mut self, hostname: String, message: String) {
-        self.stats
-            .entry(hostname)
-            .and_modify(|(count, _)| *count += 1)
-            .or_insert((1, message));
+    /// Reports the `top` most common message templates per host, 0 turns mining off.
+    pub fn with_templates(mut self, top: usize) -> Self {
+        self.top_templates = top;
+        self
+    }
+
+    /// Names sources from a hosts file, which lets relays forwarding for a
+    /// single host be told apart from that host.
+    pub fn with_hosts_file(mut self, hosts: Option<HostsFile>) -> Self {
+        self.relays = RelayTracker::new(hosts);
+        self.relays.set_max_entries(self.max_hosts);
+        self
+    }
+
+    /// Also counts messages per combination of the given keys, laid out as `output`.
+    pub fn with_groups(mut self, keys: Vec<GroupKey>, output: GroupOutput) -> Self {
+        self.groups = GroupTracker::new(keys, output);
+        self.groups.set_max_groups(self.max_hosts);
+        self
+    }
+
+    /// Tracks at most `max_hosts` hosts in detail, bounding memory when hostnames are
+    /// spoofed or garbage. Once full, a new host replaces the one with the lowest
+    /// estimated count, as in the Space-Saving algorithm (Metwally et al., 2005): any
+    /// host with more than 1/`max_hosts` of the messages is guaranteed to be reported.
+    /// Finding that host takes amortised O(log `max_hosts`) time. The budget also
+    /// caps the relay sources, authentication sources and groups tracked, and shares
+    /// the templates mined out between the hosts.
+    pub fn with_max_hosts(mut self, max_hosts: Option<usize>) -> Self {
+        self.max_hosts = max_hosts.map(|max| max.max(1));
+        self.max_templates = self.max_hosts.map_or(MAX_CLUSTERS, |max| {
+            (TEMPLATE_BUDGET / max).clamp(MIN_HOST_TEMPLATES, MAX_CLUSTERS)
+        });
+        self.relays.set_max_entries(self.max_hosts);
+        self.groups.set_max_groups(self.max_hosts);
+        self
+    }
+
+    /// Keeps random samples per host besides the first message, which is cut to
+    /// the configured maximum length as well.
+    pub fn with_samples(mut self, sampling: SampleConfig) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Adds an entry for a new host, evicting another one if the budget is reached.
+    fn insert_host(&mut self, hostname: String, sample: String) -> &mut HostEntry {
+        let sample = match self.sampling.max_length {
+            Some(_) => truncate(&sample, self.sampling.max_length),
+            None => sample,
+        };
+        let mut entry = HostEntry::new(sample, self.sampling, self.max_templates);
+        if self.max_hosts.is_some_and(|max| self.stats.len() >= max) {
+            if let Some(evicted) = self.evict_smallest() {
+                entry.count_error = evicted.count + evicted.count_error;
+                self.evicted.add(&evicted);
+            }
+        }
+        if self.max_hosts.is_some() {
+            self.by_count
+                .push(Reverse((entry.count_error, hostname.clone())));
+        }
+        self.stats.entry(hostname).or_insert(entry)
+    }
+
+    /// Removes the host with the lowest estimated count. Counts only grow, so a
+    /// bound at the top of the heap that is still current belongs to the smallest.
+    fn evict_smallest(&mut self) -> Option<HostEntry> {
+        while let Some(Reverse((bound, hostname))) = self.by_count.pop() {
+            let Some(entry) = self.stats.get(&hostname) else {
+                continue;
+            };
+            let estimate = entry.count + entry.count_error;
+            if estimate > bound {
+                self.by_count.push(Reverse((estimate, hostname)));
+            } else {
+                return self.stats.remove(&hostname);
+            }
+        }
+        None
+    }
+
+    pub fn add_entry(&mut self, hostname: impl Into<String>, message: impl Into<String>) {
+        let message = message.into();
+        let hostname = hostname.into();
+        let mine = self.top_templates > 0;
+        let entry = match self.stats.get_mut(&hostname) {
+            Some(entry) => entry,
+            None => self.insert_host(hostname, message.clone()),
+        };
+        entry.count += 1;
+        entry.sizes.add(message.len());
+        entry.add_msg(parse_header(&message).msg, mine);
+        entry.samples.add(&message, None);
+    }
+
+    /// Records a parsed packet. Only a host's first message is copied, as its sample.
+    pub fn add_packet(&mut self, hostname: &str, packet: &SyslogPacket<'_>) {
+        if !self.stats.contains_key(hostname) {
+            self.insert_host(hostname.to_string(), packet.message.to_string());
+        }
+        let Some(entry) = self.stats.get_mut(hostname) else {
+            return;
+        };
+        entry.count += 1;
+        entry.sizes.add(packet.size);
+        entry.add_msg(&packet.msg, self.top_templates > 0);
+        entry.samples.add(&packet.message, packet.severity);
+        *entry.formats.entry(packet.format).or_insert(0) += 1;
+        if let Some(count) = packet
+            .severity
+            .and_then(|severity| entry.severities.get_mut(usize::from(severity)))
+        {
+            *count += 1;
+        }
+        if let Some(count) = packet
+            .facility
+            .and_then(|facility| entry.facilities.get_mut(usize::from(facility)))
+        {
+            *count += 1;
+        }
+        if let Some(app) = packet.app_name.as_deref() {
+            count_capped(&mut entry.apps, app, MAX_FIELD_VALUES);
+        }
+        if let Some(event) = &packet.event {
+            entry.add_event(event);
+        }
+        for escape in &packet.escapes {
+            *entry.escapes.entry(*escape).or_insert(0) += 1;
+        }
+        for violation in &packet.violations {
+            *entry.violations.entry(*violation).or_insert(0) += 1;
+        }
+
+        for (name, value) in &packet.extracted {
+            count_capped(
+                entry.fields.entry(name.clone()).or_default(),
+                value,
+                MAX_FIELD_VALUES,
+            );
+        }
+
+        if let Some(structured) = &packet.structured {
+            for pointer in &self.json_fields {
+                if let Some(value) = pointer_value(structured, pointer) {
+                    count_capped(
+                        entry.fields.entry(pointer.clone()).or_default(),
+                        &value,
+                        MAX_FIELD_VALUES,
+                    );
+                }
+            }
+        }
+
+        if let Some(Event::Auth(auth)) = &packet.event {
+            self.add_auth_source(hostname, auth);
+        }
+    }
+
+    /// Counts an authentication event towards its source address. With a host
+    /// budget, sources beyond it are counted together as [`OTHER_VALUES`].
+    fn add_auth_source(&mut self, hostname: &str, auth: &AuthEvent) {
+        let Some(source) = &auth.source_ip else {
+            return;
+        };
+        let max = self.max_hosts.unwrap_or(usize::MAX);
+        let source = capped_key(&self.auth_sources, source, max);
+        if !self.auth_sources.contains_key(source) {
+            self.auth_sources
+                .insert(source.to_string(), AuthStats::default());
+        }
+        if let Some(stats) = self.auth_sources.get_mut(source) {
+            stats.record(auth);
+            count_capped(&mut stats.hosts, hostname, MAX_FIELD_VALUES);
+        }
+    }
+
+    /// Records which extraction rules matched a message already added with
+    /// [`Self::add_packet`], to show how much of a host's traffic the rules cover.
+    pub fn add_rule_matches(&mut self, hostname: &str, rules: &[&str]) {
+        let Some(entry) = self.stats.get_mut(hostname) else {
+            return;
+        };
+        if rules.is_empty() {
+            entry.rules_unmatched += 1;
+        }
+        for rule in rules {
+            *entry.rules.entry(rule.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Records when a message already added with [`Self::add_packet`] was captured.
+    pub fn add_capture_time(&mut self, hostname: &str, time: SystemTime) {
+        if let Some(entry) = self.stats.get_mut(hostname) {
+            entry.peak_rate.add(time);
+        }
+    }
+
+    /// Records the address a packet tracked under `hostname` came from.
+    /// `header_hostname` is the hostname as sent, before normalisation.
+    pub fn add_source(&mut self, source: IpAddr, header_hostname: Option<&str>, hostname: &str) {
+        self.relays.record(source, header_hostname, hostname);
+    }
+
+    /// Counts a packet tracked under `hostname` towards its group, if grouping is on.
+    pub fn add_to_groups(&mut self, hostname: &str, packet: &SyslogPacket<'_>, origin: &Origin) {
+        self.groups.add(hostname, packet, origin);
     }
 
     pub fn is_empty(&self) -> bool {
@@ -43,23 +591,92 @@ impl StatsTracker {
 
     pub fn clear(&mut self) {
         self.stats.clear();
+        self.by_count.clear();
+        self.relays.clear();
+        self.auth_sources.clear();
+        self.groups.clear();
+        self.evicted = Evicted::default();
+    }
+
+    fn totals(&self) -> FleetTotals {
+        let mut severities = self.evicted.severities;
+        let mut facilities = self.evicted.facilities;
+        for entry in self.stats.values() {
+            for (total, count) in severities.iter_mut().zip(entry.severities) {
+                *total += count;
+            }
+            for (total, count) in facilities.iter_mut().zip(entry.facilities) {
+                *total += count;
+            }
+        }
+        FleetTotals {
+            count: self.evicted.stats.count
+                + self.stats.values().map(|entry| entry.count).sum::<u64>(),
+            bytes: self.evicted.stats.bytes
+                + self
+                    .stats
+                    .values()
+                    .map(|entry| entry.sizes.bytes())
+                    .sum::<u64>(),
+            severities: histogram(&severities, &SEVERITIES),
+            facilities: histogram(&facilities, &FACILITIES),
+        }
     }
 
     pub fn get_summary(&self, interval_seconds: u64) -> JsonSummary {
+        let relays = self.relays.relays();
+        let mut via: HashMap<&str, HashMap<String, u64>> = HashMap::new();
+        for (relay, stats) in &relays {
+            for (origin, count) in &stats.origins {
+                via.entry(origin).or_default().insert(relay.clone(), *count);
+            }
+        }
+
         let mut hosts_map = HashMap::new();
-        for (hostname, (count, sample)) in &self.stats {
+        for (hostname, entry) in &self.stats {
+            let peak = entry.peak_rate.peak();
             hosts_map.insert(
                 hostname.clone(),
                 HostStats {
-                    count: *count,
-                    sample: sample.clone(),
+                    count: entry.count,
+                    count_error: entry.count_error,
+                    bytes: entry.sizes.bytes(),
+                    size: entry.sizes.stats(),
+                    rate: per_second(entry.count, interval_seconds),
+                    peak_rate: peak.map_or(0, |(count, _)| count),
+                    peak_at: peak.map(|(_, second)| second),
+                    distinct_messages: entry.distinct_messages.estimate(),
+                    distinct_templates: entry.distinct_templates.estimate(),
+                    sample: entry.sample.clone(),
+                    samples: entry.samples.samples(),
+                    severity_samples: entry.samples.by_severity(),
+                    fields: entry.fields.clone(),
+                    rules: entry.rules.clone(),
+                    rules_unmatched: entry.rules_unmatched,
+                    apps: entry.apps.clone(),
+                    formats: entry.formats.clone(),
+                    severities: histogram(&entry.severities, &SEVERITIES),
+                    facilities: histogram(&entry.facilities, &FACILITIES),
+                    templates: entry.templates.top(self.top_templates),
+                    blocked: entry.top_blocked(),
+                    audit_types: entry.audit_types.clone(),
+                    windows_events: entry.windows_events.clone(),
+                    auth: entry.auth.clone(),
+                    escapes: entry.escapes.clone(),
+                    violations: entry.violations.clone(),
+                    via: via.remove(hostname.as_str()).unwrap_or_default(),
                 },
             );
         }
 
         JsonSummary {
             interval_seconds,
+            totals: self.totals(),
             hosts: hosts_map,
+            relays,
+            auth_sources: self.auth_sources.clone(),
+            groups: self.groups.summary(),
+            other: (self.evicted.stats.hosts > 0).then(|| self.evicted.stats.clone()),
         }
     }
 }
@@ -106,4 +723,499 @@ mod tests {
         let summary = tracker.get_summary(10);
         assert!(summary.hosts.is_empty());
     }
+
+    #[test]
+    fn test_stats_tracker_json_fields() {
+        let mut tracker = StatsTracker::with_json_fields(vec!["/user".to_string()]);
+        for msg in [
+            r#"@cee: {"user":"alice"}"#,
+            r#"@cee: {"user":"alice"}"#,
+            r#"@cee: {"user":"bob"}"#,
+            "no structured payload",
+        ] {
+            let packet = crate::parse_syslog_packet(msg.as_bytes()).unwrap();
+            tracker.add_packet("host1", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let host = &summary.hosts["host1"];
+        assert_eq!(host.count, 4);
+        assert_eq!(host.fields["/user"]["alice"], 2);
+        assert_eq!(host.fields["/user"]["bob"], 1);
+        assert_eq!(host.formats[&MessageFormat::Unknown], 4);
+    }
+
+    #[test]
+    fn test_stats_tracker_gelf_label() {
+        let mut tracker = StatsTracker::new();
+        let gelf = br#"{"version":"1.1","host":"app01","short_message":"hi","level":6}"#;
+        let packet = crate::gelf::GelfDecoder::new()
+            .decode(gelf, std::time::Instant::now())
+            .unwrap();
+        tracker.add_packet("app01", &packet);
+
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.hosts["app01"].formats[&MessageFormat::Gelf], 1);
+        // Bytes count the whole GELF document, not just short_message
+        assert_eq!(summary.hosts["app01"].bytes, gelf.len() as u64);
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains(r#""formats":{"gelf":1}"#));
+    }
+
+    #[test]
+    fn test_stats_tracker_violations() {
+        let mut tracker = StatsTracker::new();
+        let options = crate::ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        for msg in [
+            "<999>Oct 11 22:14:15 router1 x",
+            "<999>Oct 01 22:14:15 router1 x",
+            "<13>Oct 11 22:14:15 router1 x",
+        ] {
+            let packet = crate::parse_syslog_packet_with(msg.as_bytes(), &options).unwrap();
+            tracker.add_packet("router1", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let violations = &summary.hosts["router1"].violations;
+        assert!(summary.hosts["router1"].apps.is_empty());
+        assert_eq!(violations[&Violation::PriOutOfRange], 2);
+        assert_eq!(violations[&Violation::BadTimestamp], 1);
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains(r#""pri_out_of_range":2"#));
+    }
+
+    #[test]
+    fn test_stats_tracker_relays() {
+        let relay = IpAddr::from([10, 0, 0, 5]);
+        let mut tracker = StatsTracker::new();
+        for (header, host) in [("web01", "web01"), ("web02", "web02"), ("-", "-")] {
+            let message = format!("<13>Oct 11 22:14:15 {header} su: x");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet(host, &packet);
+            tracker.add_source(relay, packet.hostname.as_deref(), host);
+        }
+
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.relays["10.0.0.5"].origins.len(), 2);
+        assert_eq!(summary.relays["10.0.0.5"].missing_hostname, 1);
+        assert_eq!(summary.hosts["web01"].via["10.0.0.5"], 1);
+        assert!(summary.hosts["-"].via.is_empty());
+
+        tracker.clear();
+        assert!(tracker.get_summary(10).relays.is_empty());
+    }
+
+    #[test]
+    fn test_stats_tracker_apps() {
+        let mut tracker = StatsTracker::new();
+        for msg in [
+            "<13>Oct 11 22:14:15 host1 sshd[1]: a",
+            "<13>Oct 11 22:14:15 host1 sshd[2]: b",
+            "<13>Oct 11 22:14:15 host1 CRON[3]: c",
+            "<165>1 - host1 sshd 4 - - d",
+            "<13>Oct 11 22:14:15 host1 no tag here",
+        ] {
+            let packet = crate::parse_syslog_packet(msg.as_bytes()).unwrap();
+            tracker.add_packet("host1", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let apps = &summary.hosts["host1"].apps;
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps["sshd"], 3);
+        assert_eq!(apps["CRON"], 1);
+    }
+
+    #[test]
+    fn test_stats_tracker_extracted_fields() {
+        let mut tracker = StatsTracker::new();
+        let mut packet = crate::parse_syslog_packet(b"<13>GET / 404").unwrap();
+        packet.extracted = vec![("status".to_string(), "404".to_string())];
+        tracker.add_packet("web01", &packet);
+        tracker.add_packet("web01", &packet);
+
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.hosts["web01"].fields["status"]["404"], 2);
+
+        for i in 0..MAX_FIELD_VALUES + 10 {
+            packet.extracted = vec![("status".to_string(), i.to_string())];
+            tracker.add_packet("web01", &packet);
+        }
+        let fields = &tracker.get_summary(10).hosts["web01"].fields["status"];
+        assert_eq!(fields.len(), MAX_FIELD_VALUES + 1);
+        assert_eq!(fields["404"], 2);
+        assert_eq!(fields[OTHER_VALUES], 11);
+    }
+
+    #[test]
+    fn test_stats_tracker_rule_matches() {
+        let mut tracker = StatsTracker::new();
+        let packet = crate::parse_syslog_packet(b"<13>GET / 404").unwrap();
+        for rules in [&["status"][..], &["status", "path"], &[]] {
+            tracker.add_packet("web01", &packet);
+            tracker.add_rule_matches("web01", rules);
+        }
+        tracker.add_rule_matches("unseen", &["status"]);
+
+        let summary = tracker.get_summary(10);
+        let host = &summary.hosts["web01"];
+        assert_eq!(host.rules["status"], 2);
+        assert_eq!(host.rules["path"], 1);
+        assert_eq!(host.rules_unmatched, 1);
+        assert!(!summary.hosts.contains_key("unseen"));
+    }
+
+    #[test]
+    fn test_stats_tracker_templates() {
+        let mut tracker = StatsTracker::new();
+        for user in ["alice", "bob", "carol"] {
+            let message = format!("<13>Oct 11 22:14:15 host1 sshd[1]: session opened for {user}");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("host1", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let templates = &summary.hosts["host1"].templates;
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].template, "session opened for <*>");
+        assert_eq!(templates[0].count, 3);
+        assert_eq!(templates[0].example, "session opened for alice");
+
+        let mut tracker = StatsTracker::new().with_templates(0);
+        tracker.add_entry("host1", "msg");
+        let packet = crate::parse_syslog_packet(b"<13>hello").unwrap();
+        tracker.add_packet("host1", &packet);
+        assert!(tracker.get_summary(10).hosts["host1"].templates.is_empty());
+    }
+
+    #[test]
+    fn test_stats_tracker_kernel_events() {
+        let mut tracker = StatsTracker::new();
+        for (source, prefix) in [
+            ("203.0.113.5", "[UFW BLOCK]"),
+            ("203.0.113.5", "[UFW BLOCK]"),
+            ("198.51.100.7", "[UFW BLOCK]"),
+            ("192.0.2.1", "[UFW ALLOW]"),
+        ] {
+            let message = format!(
+                "<4>Oct 11 22:14:15 fw1 kernel: {prefix} IN=eth0 OUT= SRC={source} DST=10.0.0.1 PROTO=TCP DPT=22"
+            );
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("fw1", &packet);
+        }
+        let message =
+            b"<5>Oct 11 22:14:15 fw1 audispd: type=USER_AUTH msg=audit(1.2:3): res=failed";
+        let packet = crate::parse_syslog_packet(message).unwrap();
+        tracker.add_packet("fw1", &packet);
+
+        let summary = tracker.get_summary(10);
+        let host = &summary.hosts["fw1"];
+        assert_eq!(
+            host.blocked,
+            vec![
+                FlowStats {
+                    src: "203.0.113.5".to_string(),
+                    dst: "10.0.0.1".to_string(),
+                    count: 2,
+                },
+                FlowStats {
+                    src: "198.51.100.7".to_string(),
+                    dst: "10.0.0.1".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(host.audit_types["USER_AUTH"], 1);
+        assert_eq!(host.count, 5);
+    }
+
+    #[test]
+    fn test_stats_tracker_auth() {
+        let mut tracker = StatsTracker::new();
+        for (host, message) in [
+            (
+                "bastion",
+                "sshd[1]: Failed password for root from 203.0.113.5 port 22 ssh2",
+            ),
+            (
+                "bastion",
+                "sshd[1]: Invalid user admin from 203.0.113.5 port 22",
+            ),
+            (
+                "web01",
+                "sshd[1]: Failed password for root from 203.0.113.5 port 22 ssh2",
+            ),
+            (
+                "web01",
+                "sshd[1]: Accepted publickey for alice from 10.0.0.9 port 22 ssh2",
+            ),
+            (
+                "web01",
+                "sudo: alice : TTY=pts/0 ; PWD=/ ; USER=root ; COMMAND=/bin/ls",
+            ),
+        ] {
+            let message = format!("<38>Oct 11 22:14:15 {host} {message}");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet(host, &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"].auth;
+        assert_eq!((web01.success, web01.failure), (2, 1));
+        assert_eq!(web01.users["alice"], 2);
+        assert_eq!(summary.hosts["bastion"].auth.invalid_user, 1);
+
+        let attacker = &summary.auth_sources["203.0.113.5"];
+        assert_eq!(attacker.failed(), 3);
+        assert_eq!(attacker.hosts["bastion"], 2);
+        assert_eq!(attacker.hosts["web01"], 1);
+        assert_eq!(summary.auth_sources["10.0.0.9"].success, 1);
+        // sudo has no remote address
+        assert_eq!(summary.auth_sources.len(), 2);
+
+        tracker.clear();
+        assert!(tracker.get_summary(10).auth_sources.is_empty());
+    }
+
+    #[test]
+    fn test_stats_tracker_windows_events() {
+        let mut tracker = StatsTracker::new();
+        for event_id in [4625, 4625, 4624] {
+            let message = format!(
+                "<14>WIN-DC01\tMSWinEventLog\t1\tSecurity\t7\tdate\t{event_id}\tMicrosoft-Windows-Security-Auditing"
+            );
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet(packet.hostname.as_deref().unwrap(), &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let events = &summary.hosts["WIN-DC01"].windows_events;
+        assert_eq!(events[&4625], 2);
+        assert_eq!(events[&4624], 1);
+    }
+
+    #[test]
+    fn test_stats_tracker_severities_and_facilities() {
+        let mut tracker = StatsTracker::new();
+        for (host, pri) in [("web01", 191), ("web01", 191), ("web01", 11), ("db01", 38)] {
+            let message = format!("<{pri}>Oct 11 22:14:15 {host} app: hello");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet(host, &packet);
+        }
+        tracker.add_entry("legacy", "no pri");
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!(web01.severities["debug"], 2);
+        assert_eq!(web01.severities["err"], 1);
+        assert_eq!(web01.facilities["local7"], 2);
+        assert_eq!(web01.facilities["user"], 1);
+        assert!(summary.hosts["legacy"].severities.is_empty());
+
+        let totals = &summary.totals;
+        assert_eq!(totals.count, 5);
+        assert_eq!(totals.severities.len(), 3);
+        assert_eq!(totals.severities["info"], 1);
+        assert_eq!(totals.facilities["auth"], 1);
+        assert_eq!(totals.facilities["local7"], 2);
+    }
+
+    #[test]
+    fn test_stats_tracker_sizes() {
+        let mut tracker = StatsTracker::new();
+        for message in ["<13>a", "<13>bbbbb", "<13>ccccccccc"] {
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("web01", &packet);
+        }
+        tracker.add_entry("legacy", "no pri");
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!(web01.bytes, 27);
+        assert_eq!(
+            web01.size,
+            SizeStats {
+                min: 5,
+                avg: 9,
+                max: 13,
+                p50: 9,
+                p95: 13,
+                p99: 13
+            }
+        );
+        assert_eq!(summary.hosts["legacy"].bytes, 6);
+        assert_eq!(summary.totals.bytes, 33);
+    }
+
+    #[test]
+    fn test_stats_tracker_rates() {
+        let mut tracker = StatsTracker::new();
+        let start = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        for millis in [0, 1000, 1200, 1400, 5000] {
+            let packet = crate::parse_syslog_packet(b"<13>hello").unwrap();
+            tracker.add_packet("web01", &packet);
+            let time = start + std::time::Duration::from_millis(millis);
+            tracker.add_capture_time("web01", time);
+        }
+        tracker.add_entry("legacy", "no capture time");
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!(web01.rate, 0.5);
+        assert_eq!(web01.peak_rate, 3);
+        assert_eq!(web01.peak_at, Some(1_700_000_001));
+        let legacy = &summary.hosts["legacy"];
+        assert_eq!((legacy.peak_rate, legacy.peak_at), (0, None));
+        let json = serde_json::to_string(legacy).unwrap();
+        assert!(!json.contains("peak"));
+    }
+
+    #[test]
+    fn test_stats_tracker_max_hosts() {
+        let mut tracker = StatsTracker::new().with_max_hosts(Some(2));
+        for host in [
+            "web01", "web01", "web01", "web01", "db01", "db01", "x1", "x2",
+        ] {
+            let message = format!("<11>Oct 11 22:14:15 {host} app: hello");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet(host, &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.hosts.len(), 2);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!((web01.count, web01.count_error), (4, 0));
+        // x1 replaced db01 (2 messages), then x2 replaced x1 (estimated at 3)
+        let x2 = &summary.hosts["x2"];
+        assert_eq!((x2.count, x2.count_error), (1, 3));
+        let other = summary.other.unwrap();
+        assert_eq!((other.hosts, other.count), (2, 3));
+        assert_eq!(summary.totals.count, 8);
+        assert_eq!(summary.totals.severities["err"], 8);
+
+        tracker.clear();
+        assert!(tracker.get_summary(10).other.is_none());
+        assert!(StatsTracker::new().get_summary(10).other.is_none());
+
+        // Hosts that grew since they were admitted aren't evicted
+        for host in ["a", "b", "a", "a", "a", "c"] {
+            tracker.add_entry(host, "msg");
+        }
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.hosts["a"].count, 4);
+        assert_eq!(summary.hosts["c"].count_error, 1);
+        assert!(!summary.hosts.contains_key("b"));
+    }
+
+    #[test]
+    fn test_stats_tracker_max_hosts_bounds_sources() {
+        let mut tracker = StatsTracker::new()
+            .with_max_hosts(Some(2))
+            .with_groups(vec![GroupKey::SourceIp], GroupOutput::Flat);
+        for i in 1..=5 {
+            let message =
+                format!("<38>Oct 11 22:14:15 bastion sshd[1]: Failed password for root from 10.0.0.{i} port 22 ssh2");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            let source = IpAddr::from([10, 0, 0, i]);
+            let origin = Origin {
+                source_ip: Some(source),
+                ..Origin::default()
+            };
+            tracker.add_packet("bastion", &packet);
+            tracker.add_to_groups("bastion", &packet, &origin);
+            tracker.add_source(source, Some("bastion"), "bastion");
+        }
+
+        let summary = tracker.get_summary(10);
+        assert_eq!(summary.auth_sources.len(), 3);
+        assert_eq!(summary.auth_sources[OTHER_VALUES].failure, 3);
+        assert_eq!(summary.groups.unwrap().as_array().unwrap().len(), 3);
+
+        for (max_hosts, templates) in [
+            (None, MAX_CLUSTERS),
+            (Some(2), MAX_CLUSTERS),
+            (Some(1000), 100),
+            (Some(1_000_000), MIN_HOST_TEMPLATES),
+        ] {
+            let tracker = StatsTracker::new().with_max_hosts(max_hosts);
+            assert_eq!(tracker.max_templates, templates, "{max_hosts:?}");
+        }
+    }
+
+    #[test]
+    fn test_stats_tracker_distinct() {
+        let mut tracker = StatsTracker::new();
+        for i in 0..50 {
+            let message = format!("<13>Oct 11 22:14:{:02} web01 app: same", i % 60);
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("web01", &packet);
+            let message = format!("<13>Oct 11 22:14:15 db01 app: query {i} took {i}ms");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("db01", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!((web01.distinct_messages, web01.distinct_templates), (1, 1));
+        let db01 = &summary.hosts["db01"];
+        // An estimate, collisions in the registers lose a few values
+        assert!((45..=55).contains(&db01.distinct_messages));
+        assert_eq!(db01.distinct_templates, 1);
+
+        // Templates are the mined ones, not just MSGs with digits masked
+        let mut tracker = StatsTracker::new();
+        for (second, user) in ["alice", "bob", "dave"].iter().enumerate() {
+            let message =
+                format!("<86>Oct 11 22:14:{second:02} web01 sshd: session opened for {user}");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("web01", &packet);
+            // Entries count the MSG too, not the header around it
+            tracker.add_entry("db01", message.replace("web01", "db01"));
+        }
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!((web01.distinct_messages, web01.distinct_templates), (3, 1));
+        assert_eq!(web01.templates.len(), 1);
+        let db01 = &summary.hosts["db01"];
+        assert_eq!((db01.distinct_messages, db01.distinct_templates), (3, 1));
+
+        let mut tracker = StatsTracker::new().with_templates(0);
+        tracker.add_entry("db01", "<13>Oct 11 22:14:15 db01 app: query 1 took 1ms");
+        tracker.add_entry("db01", "<13>Oct 11 22:14:16 db01 app: query 2 took 2ms");
+        assert_eq!(tracker.get_summary(10).hosts["db01"].distinct_templates, 1);
+    }
+
+    #[test]
+    fn test_stats_tracker_samples() {
+        let mut tracker = StatsTracker::new().with_samples(SampleConfig {
+            count: 3,
+            by_severity: false,
+            max_length: Some(20),
+        });
+        for i in 0..10 {
+            let message = format!("<13>Oct 11 22:14:15 web01 app: message {i}");
+            let packet = crate::parse_syslog_packet(message.as_bytes()).unwrap();
+            tracker.add_packet("web01", &packet);
+        }
+
+        let summary = tracker.get_summary(10);
+        let web01 = &summary.hosts["web01"];
+        assert_eq!(web01.sample, "<13>Oct 11 22:14:15 ... [20 bytes truncated]");
+        assert_eq!(web01.samples.len(), 3);
+        assert!(web01
+            .samples
+            .iter()
+            .all(|sample| sample.ends_with(" bytes truncated]")));
+        assert!(web01.severity_samples.is_empty());
+
+        // Without --samples the key is left out
+        let mut tracker = StatsTracker::new();
+        tracker.add_entry("web01", "<13>Oct 11 22:14:15 web01 app: message");
+        let json = serde_json::to_value(tracker.get_summary(10)).unwrap();
+        assert!(json["hosts"]["web01"].get("samples").is_none());
+        assert!(json["hosts"]["web01"].get("severity_samples").is_none());
+    }
 }
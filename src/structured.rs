@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+/// Marker used by CEE (Common Event Expression) senders, e.g. rsyslog's mmjsonparse.
+const CEE_COOKIE: &str = "@cee:";
+
+/// Detects a structured JSON payload in a syslog MSG.
+///
+/// Two forms are recognised, each only at the start of the MSG:
+/// - `@cee: {...}` (the CEE cookie)
+/// - a bare JSON object making up the whole MSG
+///
+/// Only JSON objects are accepted, scalars and arrays are ignored.
+pub fn parse_structured(msg: &str) -> Option<Value> {
+    let msg = msg.trim();
+    let candidate = match msg.strip_prefix(CEE_COOKIE) {
+        Some(payload) => payload.trim_start(),
+        // Cheap pre-check so we don't run the JSON parser on every message
+        None if msg.starts_with('{') && msg.ends_with('}') => msg,
+        None => return None,
+    };
+
+    match serde_json::from_str::<Value>(candidate) {
+        Ok(value) if value.is_object() => Some(value),
+        _ => None,
+    }
+}
+
+/// Turns `user` into the JSON pointer `/user`, so a forgotten slash still matches.
+/// The empty pointer, the whole payload, is rejected as it would group by message.
+pub fn normalize_pointer(pointer: &str) -> Result<String, String> {
+    if pointer.is_empty() {
+        Err("Invalid JSON pointer: the empty pointer selects the whole payload".to_string())
+    } else if pointer.starts_with('/') {
+        Ok(pointer.to_string())
+    } else {
+        Ok(format!("/{}", pointer))
+    }
+}
+
+/// Resolves a JSON pointer (RFC 6901) and renders the value as a grouping key.
+///
+/// Strings are returned without quotes, everything else uses its JSON form.
+pub fn pointer_value(value: &Value, pointer: &str) -> Option<String> {
+    match value.pointer(pointer)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_cee() {
+        let msg = r#"@cee: {"user":"alice","code":7}"#;
+        let value = parse_structured(msg).unwrap();
+        assert_eq!(value, json!({"user": "alice", "code": 7}));
+        assert!(parse_structured(r#" @cee:{"user":"bob"}"#).is_some());
+        // Only a cookie at the start of the MSG counts
+        assert!(parse_structured(r#"see @cee: {"user":"alice"} for the format"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_bare_json() {
+        let value = parse_structured(r#"{"event":{"id":42}} "#).unwrap();
+        assert_eq!(value["event"]["id"], 42);
+        // JSON after prose isn't the payload
+        assert!(parse_structured(r#"user {x} logged {"a":1}"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_not_json() {
+        assert!(parse_structured("<13>Oct 11 22:14:15 mymachine su: su root").is_none());
+        assert!(parse_structured("braces {but} not json }").is_none());
+        assert!(parse_structured("@cee: [1, 2, 3]").is_none());
+        assert!(parse_structured("@cee: not json").is_none());
+    }
+
+    #[test]
+    fn test_normalize_pointer() {
+        assert_eq!(normalize_pointer("user").unwrap(), "/user");
+        assert_eq!(normalize_pointer("/event/id").unwrap(), "/event/id");
+        assert!(normalize_pointer("").is_err());
+    }
+
+    #[test]
+    fn test_pointer_value() {
+        let value = json!({"user": "alice", "code": 7, "nested": {"ok": true}, "none": null});
+        assert_eq!(pointer_value(&value, "/user").as_deref(), Some("alice"));
+        assert_eq!(pointer_value(&value, "/code").as_deref(), Some("7"));
+        assert_eq!(pointer_value(&value, "/nested/ok").as_deref(), Some("true"));
+        assert!(pointer_value(&value, "/none").is_none());
+        assert!(pointer_value(&value, "/missing").is_none());
+    }
+}
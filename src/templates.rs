@@ -0,0 +1,253 @@
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Placeholder for a variable token in a template
+const PARAM: &str = "<*>";
+/// Leading tokens that pick the group a message is compared within, as in Drain's
+/// fixed-depth parse tree
+const PREFIX_TOKENS: usize = 2;
+/// Share of tokens that must match for a message to join a template
+const SIMILARITY_THRESHOLD: f64 = 0.4;
+/// Caps memory per host. Once reached, messages that match no template aren't mined.
+pub const MAX_CLUSTERS: usize = 1000;
+
+/// A mined template with the number of messages it covers.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TemplateStats {
+    pub template: String,
+    pub count: u64,
+    /// The first message that created the template
+    pub example: String,
+}
+
+#[derive(Debug)]
+struct Cluster {
+    tokens: Vec<String>,
+    count: u64,
+    example: String,
+}
+
+impl Cluster {
+    /// Number of tokens equal to the message's and number of parameters.
+    /// Parameters don't count as equal, so more specific templates win.
+    fn similarity(&self, message: &str) -> (usize, usize) {
+        self.tokens.iter().zip(message.split_whitespace()).fold(
+            (0, 0),
+            |(same, params), (template, token)| {
+                if template == PARAM {
+                    (same, params + 1)
+                } else {
+                    (same + usize::from(template == token), params)
+                }
+            },
+        )
+    }
+
+    /// Turns every token that differs from the message into a parameter.
+    fn merge(&mut self, message: &str) {
+        for (template, token) in self.tokens.iter_mut().zip(message.split_whitespace()) {
+            if template != PARAM && template != token {
+                *template = PARAM.to_string();
+            }
+        }
+    }
+}
+
+/// Online log template mining after the Drain algorithm (He et al., 2017).
+///
+/// Messages are grouped by token count and leading tokens, then joined to the most
+/// similar template in their group, which turns differing tokens into `<*>`.
+/// Messages that fit an existing template are mined without allocating.
+#[derive(Debug)]
+pub struct TemplateMiner {
+    groups: HashMap<u64, Vec<usize>>,
+    clusters: Vec<Cluster>,
+    max_clusters: usize,
+}
+
+impl Default for TemplateMiner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn group_key(message: &str, length: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    length.hash(&mut hasher);
+    for token in message.split_whitespace().take(PREFIX_TOKENS) {
+        // Tokens with digits are likely variables, don't let them split groups
+        if token.bytes().any(|b| b.is_ascii_digit()) {
+            PARAM.hash(&mut hasher);
+        } else {
+            token.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Hash of a message with every token containing a digit masked, a cheap stand-in
+/// for its template that doesn't depend on what was mined so far.
+pub fn template_hash(message: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for token in message.split_whitespace() {
+        if token.bytes().any(|b| b.is_ascii_digit()) {
+            PARAM.hash(&mut hasher);
+        } else {
+            token.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+impl TemplateMiner {
+    pub fn new() -> Self {
+        Self::with_max_clusters(MAX_CLUSTERS)
+    }
+
+    /// A miner that keeps at most `max_clusters` templates.
+    pub fn with_max_clusters(max_clusters: usize) -> Self {
+        Self {
+            groups: HashMap::new(),
+            clusters: Vec::new(),
+            max_clusters,
+        }
+    }
+
+    /// Mines a message and returns the id of the template it joined or created,
+    /// `None` once the template budget is spent.
+    pub fn add(&mut self, message: &str) -> Option<usize> {
+        let length = message.split_whitespace().count();
+        let key = group_key(message, length);
+
+        let clusters = &self.clusters;
+        let best = self.groups.get(&key).and_then(|ids| {
+            ids.iter()
+                // Different lengths can share a group on a hash collision
+                .filter(|&&id| clusters[id].tokens.len() == length)
+                .map(|&id| (clusters[id].similarity(message), id))
+                .max_by_key(|&(similarity, _)| similarity)
+                .filter(|&((same, _), _)| {
+                    length == 0 || same as f64 / length as f64 >= SIMILARITY_THRESHOLD
+                })
+                .map(|(_, id)| id)
+        });
+
+        match best {
+            Some(id) => {
+                let cluster = &mut self.clusters[id];
+                cluster.count += 1;
+                cluster.merge(message);
+                Some(id)
+            }
+            None if self.clusters.len() < self.max_clusters => {
+                let id = self.clusters.len();
+                self.groups.entry(key).or_default().push(id);
+                self.clusters.push(Cluster {
+                    tokens: message.split_whitespace().map(str::to_string).collect(),
+                    count: 1,
+                    example: message.to_string(),
+                });
+                Some(id)
+            }
+            None => None,
+        }
+    }
+
+    /// The `n` templates covering the most messages.
+    pub fn top(&self, n: usize) -> Vec<TemplateStats> {
+        let mut clusters: Vec<&Cluster> = self.clusters.iter().collect();
+        clusters.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tokens.cmp(&b.tokens)));
+        clusters
+            .into_iter()
+            .take(n)
+            .map(|cluster| TemplateStats {
+                template: cluster.tokens.join(" "),
+                count: cluster.count,
+                example: cluster.example.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mine_templates() {
+        let mut miner = TemplateMiner::new();
+        for message in [
+            "Accepted password for alice from 10.0.0.1 port 5000 ssh2",
+            "Accepted password for bob from 10.0.0.2 port 5001 ssh2",
+            "Accepted publickey for carol from 10.0.0.3 port 5002 ssh2",
+            "Connection closed by 10.0.0.9",
+            "Disk /dev/sda1 is 91% full",
+            "Disk /dev/sdb1 is 95% full",
+            "Accepted password for dave from 10.0.0.4 port 5003 ssh2",
+        ] {
+            miner.add(message);
+        }
+
+        let top = miner.top(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(
+            top[0].template,
+            "Accepted password for <*> from <*> port <*> ssh2"
+        );
+        assert_eq!(top[0].count, 3);
+        assert_eq!(
+            top[0].example,
+            "Accepted password for alice from 10.0.0.1 port 5000 ssh2"
+        );
+        assert_eq!(top[1].template, "Disk <*> is <*> full");
+        assert_eq!(top[1].count, 2);
+        // A different second token starts a separate group
+        assert_eq!(miner.top(10).len(), 4);
+        assert_eq!(
+            miner.add("Accepted password for erin from 10.0.0.5 port 5004 ssh2"),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_template_hash() {
+        assert_eq!(
+            template_hash("Disk /dev/sda1 is 91% full"),
+            template_hash("Disk  /dev/sdb1 is 5% full")
+        );
+        assert_ne!(
+            template_hash("Disk /dev/sda1 is 91% full"),
+            template_hash("Disk /dev/sda1 is 91% empty")
+        );
+    }
+
+    #[test]
+    fn test_dissimilar_messages_stay_apart() {
+        let mut miner = TemplateMiner::new();
+        miner.add("kernel: eth0 link is up again");
+        miner.add("kernel: eth0 renamed from eth1 by udev");
+        miner.add("");
+        miner.add("");
+
+        let top = miner.top(10);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0].template, "");
+        assert_eq!(top[0].count, 2);
+        assert!(top.iter().all(|t| !t.template.contains(PARAM)));
+    }
+
+    #[test]
+    fn test_cluster_limit() {
+        let mut miner = TemplateMiner::new();
+        for i in 0..MAX_CLUSTERS + 10 {
+            // Every message lands in its own group and template
+            miner.add(&"x ".repeat(i + 1));
+        }
+        assert_eq!(miner.clusters.len(), MAX_CLUSTERS);
+        assert_eq!(miner.add(&"x ".repeat(MAX_CLUSTERS + 20)), None);
+        assert_eq!(miner.add("x"), Some(0));
+        assert_eq!(miner.top(1)[0].count, 2);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::events::{find_token, key_values};
+use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Snare's marker in front of its tab-delimited fields
+const SNARE_MARKER: &str = "MSWinEventLog\t";
+
+/// A Windows event log record forwarded over syslog.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WindowsEvent {
+    pub event_id: u32,
+    /// The event log, e.g. `Security` or `System`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// e.g. `Microsoft-Windows-Security-Auditing`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// The name of the computer that logged the event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub computer: Option<String>,
+}
+
+/// Recognises Snare `MSWinEventLog` records and NXLog's key-value output, or NXLog
+/// JSON if the message carried a JSON payload.
+pub fn parse_windows(msg: &str, structured: Option<&Value>) -> Option<WindowsEvent> {
+    parse_snare(msg)
+        .or_else(|| structured.and_then(parse_nxlog_json))
+        .or_else(|| parse_nxlog_kv(msg))
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty() && value != "N/A").then(|| value.to_string())
+}
+
+/// `MSWinEventLog<TAB>criticality<TAB>channel<TAB>counter<TAB>time<TAB>event id
+/// <TAB>provider<TAB>user<TAB>SID type<TAB>event type<TAB>computer<TAB>...`
+fn parse_snare(msg: &str) -> Option<WindowsEvent> {
+    let start = msg.find(SNARE_MARKER)?;
+    let fields: Vec<&str> = msg[start..].split('\t').collect();
+    Some(WindowsEvent {
+        event_id: fields.get(5)?.trim().parse().ok()?,
+        channel: non_empty(fields[2]),
+        provider: fields.get(6).and_then(|provider| non_empty(provider)),
+        // Without a syslog header the computer name also goes in front of the marker
+        computer: fields
+            .get(10)
+            .and_then(|computer| non_empty(computer))
+            .or_else(|| non_empty(&msg[..start]).filter(|host| !host.contains(' '))),
+    })
+}
+
+/// Builds an event from NXLog's im_msvistalog field names.
+fn from_fields<'a>(fields: impl Iterator<Item = (&'a str, Cow<'a, str>)>) -> Option<WindowsEvent> {
+    let mut event_id = None;
+    let mut event = WindowsEvent {
+        event_id: 0,
+        channel: None,
+        provider: None,
+        computer: None,
+    };
+    for (key, value) in fields {
+        match key {
+            "EventID" => event_id = value.trim().parse().ok(),
+            "Channel" => event.channel = non_empty(&value),
+            "SourceName" | "ProviderName" => event.provider = non_empty(&value),
+            "Hostname" | "Computer" => event.computer = non_empty(&value),
+            _ => {}
+        }
+    }
+    event.event_id = event_id?;
+    // EventID alone is too common a key to call this a Windows event
+    (event.channel.is_some() || event.provider.is_some()).then_some(event)
+}
+
+fn parse_nxlog_json(value: &Value) -> Option<WindowsEvent> {
+    let object = value.as_object()?;
+    if !object.contains_key("EventID") {
+        return None;
+    }
+    from_fields(object.iter().filter_map(|(key, value)| {
+        let value = match value {
+            Value::String(s) => Cow::Borrowed(s.as_str()),
+            Value::Number(n) => Cow::Owned(n.to_string()),
+            _ => return None,
+        };
+        Some((key.as_str(), value))
+    }))
+}
+
+/// `EventTime="2024-10-11 22:14:15" Hostname="WIN-DC01" EventID=4625 Channel="Security"`
+fn parse_nxlog_kv(msg: &str) -> Option<WindowsEvent> {
+    find_token(msg, "EventID=")?;
+    from_fields(key_values(msg).map(|(key, value)| (key, Cow::Borrowed(value))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_snare() {
+        let msg = "MSWinEventLog\t1\tSecurity\t7418\tFri Oct 11 22:14:15 2024\t4625\t\
+                   Microsoft-Windows-Security-Auditing\tN/A\tN/A\tFailure Audit\tWIN-DC01\t\
+                   Logon\t\tAn account failed to log on.\t12";
+        let event = parse_windows(msg, None).unwrap();
+        assert_eq!(event.event_id, 4625);
+        assert_eq!(event.channel.as_deref(), Some("Security"));
+        assert_eq!(
+            event.provider.as_deref(),
+            Some("Microsoft-Windows-Security-Auditing")
+        );
+        assert_eq!(event.computer.as_deref(), Some("WIN-DC01"));
+
+        // Without a syslog header Snare starts with the computer name
+        let event =
+            parse_windows("WIN-FS01\tMSWinEventLog\t0\tSystem\t1\tdate\t7036", None).unwrap();
+        assert_eq!(event.event_id, 7036);
+        assert_eq!(event.computer.as_deref(), Some("WIN-FS01"));
+
+        assert_eq!(
+            parse_windows("MSWinEventLog\t1\tSecurity\t1\tdate\tx", None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_nxlog() {
+        let value = json!({
+            "EventTime": "2024-10-11 22:14:15",
+            "Hostname": "WIN-DC01.corp.example.com",
+            "EventID": 4624,
+            "SourceName": "Microsoft-Windows-Security-Auditing",
+            "Channel": "Security"
+        });
+        let event = parse_windows("", Some(&value)).unwrap();
+        assert_eq!(event.event_id, 4624);
+        assert_eq!(event.computer.as_deref(), Some("WIN-DC01.corp.example.com"));
+
+        let msg = r#"Hostname="WIN-WEB1" EventID=1000 SourceName="Application Error" Channel="Application""#;
+        let event = parse_windows(msg, None).unwrap();
+        assert_eq!(event.event_id, 1000);
+        assert_eq!(event.provider.as_deref(), Some("Application Error"));
+        assert_eq!(event.channel.as_deref(), Some("Application"));
+        assert_eq!(event.computer.as_deref(), Some("WIN-WEB1"));
+
+        assert_eq!(parse_windows("EventID=12 status=ok", None), None);
+        assert_eq!(parse_windows("", Some(&json!({"EventID": 12}))), None);
+    }
+}
@@ -0,0 +1,60 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use syslog_sniffer::parse_syslog_packet;
+use syslog_sniffer::stats::StatsTracker;
+
+// Counts allocations made by the current thread, so the test harness doesn't interfere
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations<F: FnMut()>(mut f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_hot_path_does_not_allocate() {
+    let messages: [&[u8]; 3] = [
+        b"<13>Oct 11 22:14:15 mymachine su: su root",
+        b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - hello",
+        b"<13>Hello world",
+    ];
+    let mut stats = StatsTracker::new();
+
+    // Make sure the counter actually sees this thread's allocations
+    assert_eq!(allocations(|| drop(Vec::<u8>::with_capacity(8))), 1);
+
+    // Warm up lazily initialised regexes and insert each host once
+    for message in messages {
+        let packet = parse_syslog_packet(message).unwrap();
+        stats.add_packet(packet.hostname.as_deref().unwrap_or("Unknown"), &packet);
+    }
+
+    let count = allocations(|| {
+        for _ in 0..100 {
+            for message in messages {
+                let packet = parse_syslog_packet(message).unwrap();
+                stats.add_packet(packet.hostname.as_deref().unwrap_or("Unknown"), &packet);
+            }
+        }
+    });
+    assert_eq!(count, 0);
+}